@@ -0,0 +1,79 @@
+//! Times full frames from a few camera poses. Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+use wolf_rust::camera::Camera;
+use wolf_rust::render::Renderer;
+use wolf_rust::span::simd_available;
+use wolf_rust::sprite::SPRITES;
+use wolf_rust::texture::{load_textures, Texture};
+
+const FRAMES: u32 = 50;
+const RESOLUTIONS: [(usize, usize); 3] = [(320, 240), (640, 480), (1920, 1080)];
+// where the floor and ceiling cover the most pixels
+const LARGE_RESOLUTIONS: [(usize, usize); 2] = [(1920, 1080), (3840, 2160)];
+
+fn main() {
+    let textures = load_textures().unwrap();
+
+    for &(width, height) in RESOLUTIONS.iter() {
+        let mut renderer = Renderer::new(width, height);
+        let mut thread_counts = vec![1, renderer.threads()];
+        thread_counts.dedup();
+
+        for &threads in thread_counts.iter() {
+            renderer.set_threads(threads);
+            println!(
+                "{}x{} on {} thread(s): {:.3} ms/frame",
+                width,
+                height,
+                threads,
+                time_frame(&mut renderer, &textures).as_secs_f64() * 1000.0
+            );
+        }
+    }
+
+    if !simd_available() {
+        println!("no vectorized floor texel lookup on this CPU");
+        return;
+    }
+    for &(width, height) in LARGE_RESOLUTIONS.iter() {
+        let mut renderer = Renderer::new(width, height);
+        renderer.set_threads(1);
+        for &simd in [false, true].iter() {
+            renderer.set_simd(simd);
+            println!(
+                "{}x{} {} floor texels: {:.3} ms/frame",
+                width,
+                height,
+                if simd { "vectorized" } else { "scalar" },
+                time_frame(&mut renderer, &textures).as_secs_f64() * 1000.0
+            );
+        }
+    }
+}
+
+/// The average time `renderer` takes to render a frame from each of the poses.
+fn time_frame(renderer: &mut Renderer, textures: &[Texture]) -> Duration {
+    let poses = [
+        Camera::default(),
+        Camera::new(18.5, 2.5, -1.0, -0.4),
+        Camera::new(20.5, 1.5, 0.0, 1.0),
+    ];
+    let (width, height) = (renderer.width(), renderer.height());
+    let mut texture_buffer = vec![0; width * height * 3];
+    let mut total = Duration::default();
+    for pose in poses.iter() {
+        let mut camera = *pose;
+        camera.set_aspect_ratio(width, height);
+        // warm up
+        renderer.render(&camera, textures, &SPRITES, &mut texture_buffer);
+
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            renderer.render(&camera, textures, &SPRITES, &mut texture_buffer);
+        }
+        total += start.elapsed();
+    }
+
+    total / (FRAMES * poses.len() as u32)
+}
@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+
+/// Something the player can do, whichever key or script it comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    TurnLeft,
+    TurnRight,
+    NarrowFov,
+    WidenFov,
+    ToggleMipmaps,
+    ToggleFiltering,
+    CycleSupersampling,
+    ToggleFixedPoint,
+    TogglePostProcessing,
+    CycleStereo,
+    Shoot,
+    Quit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+    Pressed(Action),
+    Released(Action),
+}
+
+/// Where frames are shown and input comes from, so the same game loop can run in a window, in
+/// a terminal or with no display at all.
+pub trait Backend {
+    /// Shows a finished RGB24 frame of `width * height` pixels.
+    fn present(&mut self, frame: &[u8], width: usize, height: usize) -> Result<(), String>;
+
+    /// Adds the input that came in since the last call to `events`.
+    fn poll_events(&mut self, events: &mut Vec<InputEvent>) -> Result<(), String>;
+
+    /// Seconds since the last call, which movement is scaled by.
+    fn frame_time(&mut self) -> f64;
+
+    /// The size frames should be rendered at, for backends that pick it themselves.
+    fn resolution(&mut self) -> Option<(usize, usize)> {
+        None
+    }
+}
+
+/// Runs without a display. Every frame goes to a sink, and input comes from a script of events
+/// tagged with the frame they happen after. Each frame lasts a 60th of a second, and once the
+/// script runs out it quits.
+pub struct Headless<F> {
+    sink: F,
+    script: VecDeque<(usize, InputEvent)>,
+    frame: usize,
+}
+
+impl<F: FnMut(&[u8], usize, usize) -> Result<(), String>> Headless<F> {
+    /// Frames are passed to `sink` as they're presented.
+    pub fn new(mut script: Vec<(usize, InputEvent)>, sink: F) -> Headless<F> {
+        script.sort_by_key(|&(frame, _)| frame);
+        Headless {
+            sink,
+            script: script.into(),
+            frame: 0,
+        }
+    }
+}
+
+impl<F: FnMut(&[u8], usize, usize) -> Result<(), String>> Backend for Headless<F> {
+    fn present(&mut self, frame: &[u8], width: usize, height: usize) -> Result<(), String> {
+        (self.sink)(frame, width, height)?;
+        self.frame += 1;
+        Ok(())
+    }
+
+    fn poll_events(&mut self, events: &mut Vec<InputEvent>) -> Result<(), String> {
+        while let Some(&(frame, event)) = self.script.front() {
+            if frame >= self.frame {
+                return Ok(());
+            }
+            events.push(event);
+            self.script.pop_front();
+        }
+        events.push(InputEvent::Pressed(Action::Quit));
+        Ok(())
+    }
+
+    fn frame_time(&mut self) -> f64 {
+        1.0 / 60.0
+    }
+}
+
+/// Parses a script for `Headless`, one `<frame> <press|release> <action>` per line. Blank lines
+/// and lines starting with `#` are skipped.
+pub fn parse_script(text: &str) -> Result<Vec<(usize, InputEvent)>, String> {
+    let mut script = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let error = || {
+            format!(
+                "line {}: expected <frame> <press|release> <action>",
+                number + 1
+            )
+        };
+        let mut parts = line.split_whitespace();
+        let (frame, kind, action) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(frame), Some(kind), Some(action), None) => (frame, kind, action),
+            _ => return Err(error()),
+        };
+        let frame = frame.parse::<usize>().map_err(|_| error())?;
+        let action = parse_action(action)
+            .ok_or_else(|| format!("line {}: unknown action {}", number + 1, action))?;
+        let event = match kind {
+            "press" => InputEvent::Pressed(action),
+            "release" => InputEvent::Released(action),
+            _ => return Err(error()),
+        };
+        script.push((frame, event));
+    }
+
+    Ok(script)
+}
+
+fn parse_action(name: &str) -> Option<Action> {
+    Some(match name {
+        "forward" => Action::MoveForward,
+        "backward" => Action::MoveBackward,
+        "left" => Action::TurnLeft,
+        "right" => Action::TurnRight,
+        "narrow-fov" => Action::NarrowFov,
+        "widen-fov" => Action::WidenFov,
+        "mipmaps" => Action::ToggleMipmaps,
+        "filtering" => Action::ToggleFiltering,
+        "supersampling" => Action::CycleSupersampling,
+        "fixed-point" => Action::ToggleFixedPoint,
+        "post-processing" => Action::TogglePostProcessing,
+        "stereo" => Action::CycleStereo,
+        "shoot" => Action::Shoot,
+        "quit" => Action::Quit,
+        _ => return None,
+    })
+}
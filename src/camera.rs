@@ -0,0 +1,145 @@
+use crate::map::{is_solid, portal, Face, PORTALS};
+use std::cmp::Ordering;
+
+/// 2·atan(0.66) in degrees, the field of view of the original camera plane of length 0.66.
+pub const DEFAULT_FOV: f64 = 66.84962236520761;
+pub const MIN_FOV: f64 = 60.0;
+pub const MAX_FOV: f64 = 110.0;
+// the aspect ratio the field of view is given for, wider screens see more
+const BASE_ASPECT_RATIO: f64 = 4.0 / 3.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub pos_x: f64,
+    pub pos_y: f64,
+    pub dir_x: f64,
+    pub dir_y: f64,
+    pub plane_x: f64,
+    pub plane_y: f64,
+    // horizontal field of view in degrees at the base aspect ratio
+    fov: f64,
+    aspect_ratio: f64,
+}
+
+impl Camera {
+    /// Builds a camera at the given position looking along `(dir_x, dir_y)`, with the camera
+    /// plane perpendicular to it.
+    pub fn new(pos_x: f64, pos_y: f64, dir_x: f64, dir_y: f64) -> Camera {
+        let mut camera = Camera {
+            pos_x,
+            pos_y,
+            dir_x: 0.0,
+            dir_y: 0.0,
+            plane_x: 0.0,
+            plane_y: 0.0,
+            fov: DEFAULT_FOV,
+            aspect_ratio: BASE_ASPECT_RATIO,
+        };
+        camera.set_direction(dir_x, dir_y);
+        camera
+    }
+
+    /// Points the camera along `(dir_x, dir_y)`, keeping its field of view.
+    pub fn set_direction(&mut self, dir_x: f64, dir_y: f64) {
+        let length = (dir_x * dir_x + dir_y * dir_y).sqrt();
+        self.dir_x = dir_x / length;
+        self.dir_y = dir_y / length;
+        self.update_plane();
+    }
+
+    pub fn fov(&self) -> f64 {
+        self.fov
+    }
+
+    /// Sets the horizontal field of view in degrees, clamped to `MIN_FOV..=MAX_FOV`.
+    pub fn set_fov(&mut self, fov: f64) {
+        self.fov = fov.clamp(MIN_FOV, MAX_FOV);
+        self.update_plane();
+    }
+
+    pub fn move_forward(&mut self, distance: f64) {
+        let (step_x, step_y) = (self.dir_x * distance, self.dir_y * distance);
+        let turns = self.slide(step_x, 0.0);
+        // the rest of the step turns with the player when they went through a portal
+        let (step_x, step_y) = (0..turns).fold((0.0, step_y), |(x, y), _| (-y, x));
+        self.slide(step_x, step_y);
+    }
+
+    /// Moves by `step_x`, `step_y`, which is along one axis, unless that ends up in a wall.
+    /// Going through a portal comes out on its other side, and returns how many quarter turns
+    /// it turned the camera by.
+    fn slide(&mut self, step_x: f64, step_y: f64) -> u8 {
+        let (x, y) = (self.pos_x + step_x, self.pos_y + step_y);
+        let (map_x, map_y) = (self.pos_x as usize, self.pos_y as usize);
+        let face = match ((x as usize).cmp(&map_x), (y as usize).cmp(&map_y)) {
+            (Ordering::Less, _) => Some(Face::MinX),
+            (Ordering::Greater, _) => Some(Face::MaxX),
+            (_, Ordering::Less) => Some(Face::MinY),
+            (_, Ordering::Greater) => Some(Face::MaxY),
+            _ => None,
+        };
+
+        match face.and_then(|face| portal(map_x, map_y, face)) {
+            Some(index) => {
+                let portal = &PORTALS[index];
+                let (x, y) = portal.point(x, y);
+                if is_solid(x, y) {
+                    return 0;
+                }
+                self.pos_x = x;
+                self.pos_y = y;
+                (self.dir_x, self.dir_y) = portal.turn(self.dir_x, self.dir_y);
+                (self.plane_x, self.plane_y) = portal.turn(self.plane_x, self.plane_y);
+                portal.turns
+            }
+            None => {
+                if !is_solid(x, y) {
+                    self.pos_x = x;
+                    self.pos_y = y;
+                }
+                0
+            }
+        }
+    }
+
+    /// Widens or narrows the horizontal field of view to fit a `width` by `height` view, so
+    /// the vertical field of view stays the same across aspect ratios.
+    pub fn set_aspect_ratio(&mut self, width: usize, height: usize) {
+        self.aspect_ratio = width as f64 / height as f64;
+        self.update_plane();
+    }
+
+    fn update_plane(&mut self) {
+        let length = (self.fov.to_radians() / 2.0).tan() * self.aspect_ratio / BASE_ASPECT_RATIO;
+        self.plane_x = self.dir_y * length;
+        self.plane_y = -self.dir_x * length;
+    }
+
+    /// The camera moved `offset` to the right along the camera plane, where one eye of a stereo
+    /// pair sees from.
+    pub fn eye(&self, offset: f64) -> Camera {
+        let length = (self.plane_x * self.plane_x + self.plane_y * self.plane_y).sqrt();
+        Camera {
+            pos_x: self.pos_x + self.plane_x / length * offset,
+            pos_y: self.pos_y + self.plane_y / length * offset,
+            ..*self
+        }
+    }
+
+    /// Rotates the camera counter-clockwise by `angle` radians.
+    pub fn rotate(&mut self, angle: f64) {
+        let (sin, cos) = angle.sin_cos();
+        let old_dir_x = self.dir_x;
+        self.dir_x = self.dir_x * cos - self.dir_y * sin;
+        self.dir_y = old_dir_x * sin + self.dir_y * cos;
+        let old_plane_x = self.plane_x;
+        self.plane_x = self.plane_x * cos - self.plane_y * sin;
+        self.plane_y = old_plane_x * sin + self.plane_y * cos;
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera::new(22.0, 11.5, -1.0, 0.0)
+    }
+}
@@ -0,0 +1,89 @@
+use crate::map::Face;
+use std::collections::VecDeque;
+
+/// How many decals can be stamped on while playing before the oldest start to go.
+pub const DECAL_CAPACITY: usize = 64;
+
+/// An image stamped onto one face of a block, like a bullet hole or a poster. Black texels
+/// are left out, like they are in sprites.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decal {
+    pub cell: (usize, usize),
+    pub face: Face,
+    /// Where the middle of the decal is across and down the face, from 0 to 1, left to right
+    /// and top to bottom as seen from outside.
+    pub u: f64,
+    pub v: f64,
+    /// How wide and tall the decal is, as a fraction of the face.
+    pub size: f64,
+    /// An index into the textures, like `Sprite::texture`.
+    pub texture: usize,
+}
+
+impl Decal {
+    /// How far across the decal's texture the point `u` across its face is, from 0 to 1, if
+    /// it's on the decal.
+    pub fn texture_x(&self, u: f64) -> Option<f64> {
+        Some((u - self.u) / self.size + 0.5).filter(|x| (0.0..1.0).contains(x))
+    }
+
+    /// How far down the decal's texture the point `v` down its face is, from 0 to 1, if it's
+    /// on the decal.
+    pub fn texture_y(&self, v: f64) -> Option<f64> {
+        Some((v - self.v) / self.size + 0.5).filter(|y| (0.0..1.0).contains(y))
+    }
+}
+
+/// The decals stamped on while playing. Once there are `capacity` of them, each new one
+/// replaces the oldest.
+pub struct Decals {
+    decals: VecDeque<Decal>,
+    capacity: usize,
+}
+
+impl Decals {
+    pub fn new(capacity: usize) -> Decals {
+        Decals {
+            decals: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.decals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decals.is_empty()
+    }
+
+    /// Stamps on `decal`, evicting the oldest one when the pool is full.
+    pub fn add(&mut self, decal: Decal) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.decals.len() == self.capacity {
+            self.decals.pop_front();
+        }
+        self.decals.push_back(decal);
+    }
+
+    pub fn clear(&mut self) {
+        self.decals.clear();
+    }
+
+    /// Every decal, oldest first, so newer ones are drawn over them.
+    pub fn iter(&self) -> impl Iterator<Item = &Decal> {
+        self.decals.iter()
+    }
+}
+
+impl Default for Decals {
+    fn default() -> Decals {
+        Decals::new(DECAL_CAPACITY)
+    }
+}
@@ -0,0 +1,205 @@
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+const FRACTION_BITS: u32 = 16;
+
+/// A 16.16 fixed-point number. Every operation is plain integer math, so the same inputs give
+/// the same result on any compiler and CPU. Results that don't fit saturate instead of
+/// wrapping, and dividing by zero gives the largest value of the right sign.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i32);
+
+fn saturate(value: i64) -> Fixed {
+    Fixed(value.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0.saturating_add(other.0))
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0.saturating_sub(other.0))
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, other: Fixed) -> Fixed {
+        // rounded to the nearest, so long chains of products don't drift downward
+        let product = self.0 as i64 * other.0 as i64;
+        saturate((product + (1 << (FRACTION_BITS - 1))) >> FRACTION_BITS)
+    }
+}
+
+impl Div for Fixed {
+    type Output = Fixed;
+
+    fn div(self, other: Fixed) -> Fixed {
+        if other.0 == 0 {
+            return Fixed(if self.0 < 0 { i32::MIN } else { i32::MAX });
+        }
+        saturate(((self.0 as i64) << FRACTION_BITS) / other.0 as i64)
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+
+    fn neg(self) -> Fixed {
+        Fixed(self.0.saturating_neg())
+    }
+}
+
+/// The arithmetic the renderer is written against, so the same casting code runs on `f64` or
+/// on `Fixed`.
+pub trait Scalar:
+    Copy
+    + Default
+    + PartialOrd
+    + Send
+    + Sync
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    fn from_f64(value: f64) -> Self;
+    fn from_i32(value: i32) -> Self;
+    fn to_f64(self) -> f64;
+    /// Rounds toward zero, saturating like an `as` cast.
+    fn to_i32(self) -> i32;
+    fn floor(self) -> Self;
+    fn abs(self) -> Self;
+    fn hypot(self, other: Self) -> Self;
+    /// Zero for negative numbers.
+    fn sqrt(self) -> Self;
+    /// The angle of the point `other`, `self` in radians, from -pi to pi.
+    fn atan2(self, other: Self) -> Self;
+    /// The sine of an angle in radians.
+    fn sin(self) -> Self;
+}
+
+impl Scalar for f64 {
+    fn from_f64(value: f64) -> f64 {
+        value
+    }
+
+    fn from_i32(value: i32) -> f64 {
+        value as f64
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn to_i32(self) -> i32 {
+        self as i32
+    }
+
+    fn floor(self) -> f64 {
+        f64::floor(self)
+    }
+
+    fn abs(self) -> f64 {
+        f64::abs(self)
+    }
+
+    fn hypot(self, other: f64) -> f64 {
+        f64::hypot(self, other)
+    }
+
+    fn sqrt(self) -> f64 {
+        f64::sqrt(self.max(0.0))
+    }
+
+    fn atan2(self, other: f64) -> f64 {
+        f64::atan2(self, other)
+    }
+
+    fn sin(self) -> f64 {
+        f64::sin(self)
+    }
+}
+
+impl Scalar for Fixed {
+    fn from_f64(value: f64) -> Fixed {
+        saturate((value * (1 << FRACTION_BITS) as f64).round() as i64)
+    }
+
+    fn from_i32(value: i32) -> Fixed {
+        saturate((value as i64) << FRACTION_BITS)
+    }
+
+    fn to_f64(self) -> f64 {
+        self.0 as f64 / (1 << FRACTION_BITS) as f64
+    }
+
+    fn to_i32(self) -> i32 {
+        self.0 / (1 << FRACTION_BITS)
+    }
+
+    fn floor(self) -> Fixed {
+        Fixed(self.0 & !((1 << FRACTION_BITS) - 1))
+    }
+
+    fn abs(self) -> Fixed {
+        Fixed(self.0.saturating_abs())
+    }
+
+    fn hypot(self, other: Fixed) -> Fixed {
+        // the squares carry 32 fraction bits, so their root carries 16
+        let x = self.0.unsigned_abs() as u64;
+        let y = other.0.unsigned_abs() as u64;
+        saturate((x * x + y * y).isqrt() as i64)
+    }
+
+    fn sqrt(self) -> Fixed {
+        Fixed(((self.0.max(0) as u64) << FRACTION_BITS).isqrt() as i32)
+    }
+
+    fn atan2(self, other: Fixed) -> Fixed {
+        // atan(z) is close to z * (pi / 4 + 0.273 * (1 - |z|)) for |z| up to 1, to within a
+        // quarter of a degree. Steeper angles are found from the other axis.
+        let atan = |z: Fixed| {
+            z * (Fixed::from_f64(FRAC_PI_4)
+                + Fixed::from_f64(0.273) * (Fixed::from_i32(1) - z.abs()))
+        };
+        let (y, x) = (self, other);
+        let zero = Fixed::default();
+        if x == zero && y == zero {
+            zero
+        } else if x.abs() >= y.abs() {
+            let angle = atan(y / x);
+            if x > zero {
+                angle
+            } else if y >= zero {
+                angle + Fixed::from_f64(PI)
+            } else {
+                angle - Fixed::from_f64(PI)
+            }
+        } else if y > zero {
+            Fixed::from_f64(FRAC_PI_2) - atan(x / y)
+        } else {
+            -Fixed::from_f64(FRAC_PI_2) - atan(x / y)
+        }
+    }
+
+    fn sin(self) -> Fixed {
+        // brought into -pi..pi, where the parabola 4x(pi - |x|) / pi^2 and a second pass
+        // that pulls it toward the curve land within a thousandth of sin(x)
+        let turn = Fixed::from_f64(2.0 * PI).0 as i64;
+        let half_turn = Fixed::from_f64(PI).0 as i64;
+        let x = Fixed(((self.0 as i64 + half_turn).rem_euclid(turn) - half_turn) as i32);
+        let y = Fixed::from_f64(4.0 / PI) * x - Fixed::from_f64(4.0 / (PI * PI)) * x * x.abs();
+        Fixed::from_f64(0.225) * (y * y.abs() - y) + y
+    }
+}
@@ -0,0 +1,122 @@
+use crate::backend::{Action, Backend, InputEvent};
+use crate::camera::Camera;
+use crate::decal::Decal;
+use crate::map::trace;
+use crate::render::Renderer;
+use crate::sprite::SPRITES;
+use crate::stereo::Stereo;
+use crate::texture::{texture, Filtering, Texture};
+use std::collections::HashSet;
+
+// degrees the field of view changes by per key press
+const FOV_STEP: f64 = 5.0;
+// the texture of the holes shooting leaves in walls, and how big they are next to a face
+const BULLET_HOLE_TEXTURE: usize = texture("bullethole");
+const BULLET_HOLE_SIZE: f64 = 0.15;
+
+/// Renders and shows frames through `backend` and moves `camera` with its input, until it asks
+/// to quit.
+pub fn run(
+    backend: &mut impl Backend,
+    renderer: &mut Renderer,
+    camera: &mut Camera,
+    textures: &[Texture],
+) -> Result<(), String> {
+    let mut texture_buffer = Vec::new();
+    let mut events = Vec::new();
+    let mut held = HashSet::new();
+
+    loop {
+        if let Some((width, height)) = backend.resolution() {
+            if (width, height) != (renderer.width(), renderer.height()) {
+                renderer.set_size(width, height);
+                camera.set_aspect_ratio(width, height);
+            }
+        }
+        let (width, height) = (renderer.width(), renderer.height());
+        texture_buffer.resize(width * height * 3, 0);
+
+        renderer.render(camera, textures, &SPRITES, &mut texture_buffer);
+        backend.present(&texture_buffer, width, height)?;
+
+        let frame_time = backend.frame_time();
+        renderer.set_time(renderer.time() + frame_time);
+        events.clear();
+        backend.poll_events(&mut events)?;
+        for &event in events.iter() {
+            match event {
+                InputEvent::Pressed(action) => {
+                    match action {
+                        Action::Quit => return Ok(()),
+                        Action::NarrowFov => camera.set_fov(camera.fov() - FOV_STEP),
+                        Action::WidenFov => camera.set_fov(camera.fov() + FOV_STEP),
+                        Action::ToggleMipmaps => renderer.set_mipmapping(!renderer.mipmapping()),
+                        Action::ToggleFiltering => {
+                            renderer.set_filtering(match renderer.filtering() {
+                                Filtering::Nearest => Filtering::Bilinear,
+                                Filtering::Bilinear => Filtering::Nearest,
+                            })
+                        }
+                        Action::CycleSupersampling => {
+                            renderer.set_supersampling(match renderer.supersampling() {
+                                1 => 2,
+                                2 => 4,
+                                _ => 1,
+                            })
+                        }
+                        Action::ToggleFixedPoint => {
+                            renderer.set_fixed_point(!renderer.fixed_point())
+                        }
+                        Action::TogglePostProcessing => {
+                            renderer.set_post_processing(!renderer.post_processing())
+                        }
+                        Action::CycleStereo => renderer.set_stereo(match renderer.stereo() {
+                            Stereo::Off => Stereo::Anaglyph,
+                            Stereo::Anaglyph => Stereo::SideBySide,
+                            Stereo::SideBySide => Stereo::Off,
+                        }),
+                        Action::Shoot => {
+                            // the camera is half way up the walls, so that's where shots land
+                            if let Some(hit) =
+                                trace(camera.pos_x, camera.pos_y, camera.dir_x, camera.dir_y)
+                            {
+                                renderer.decals_mut().add(Decal {
+                                    cell: hit.cell,
+                                    face: hit.face,
+                                    u: hit.u,
+                                    v: 0.5,
+                                    size: BULLET_HOLE_SIZE,
+                                    texture: BULLET_HOLE_TEXTURE,
+                                });
+                            }
+                        }
+                        _ => (),
+                    }
+                    let _ = held.insert(action);
+                }
+                InputEvent::Released(action) => {
+                    let _ = held.remove(&action);
+                }
+            }
+        }
+
+        let move_speed = frame_time * 5.0;
+        let rot_speed = frame_time * 3.0;
+
+        if held.contains(&Action::MoveForward) {
+            camera.move_forward(move_speed);
+        }
+
+        if held.contains(&Action::MoveBackward) {
+            camera.move_forward(-move_speed);
+        }
+
+        if held.contains(&Action::TurnRight) {
+            camera.rotate(-rot_speed);
+        }
+
+        if held.contains(&Action::TurnLeft) {
+            camera.rotate(rot_speed);
+        }
+    }
+}
@@ -0,0 +1,16 @@
+pub mod backend;
+pub mod camera;
+pub mod decal;
+pub mod fixed;
+pub mod game;
+pub mod map;
+pub mod postprocess;
+pub mod render;
+pub mod screenshot;
+pub mod span;
+pub mod sprite;
+pub mod stereo;
+pub mod supersample;
+#[cfg(unix)]
+pub mod terminal;
+pub mod texture;
@@ -0,0 +1,498 @@
+use crate::decal::Decal;
+use crate::fixed::Scalar;
+use crate::texture::texture;
+
+pub const MAP_WIDTH: usize = 24;
+pub const MAP_HEIGHT: usize = 24;
+
+#[rustfmt::skip]
+pub const WORLD_MAP: [[usize; MAP_WIDTH]; MAP_HEIGHT] = [
+    [8,8,8,8,8,8,8,8,8,8,8,4,4,6,4,4,6,4,6,4,4,4,6,4],
+    [8,0,0,0,0,0,0,0,0,0,8,4,0,0,0,0,0,0,0,0,0,0,0,4],
+    [8,0,3,3,0,0,0,0,0,8,8,4,0,0,0,4,0,0,0,4,0,0,0,6],
+    [8,0,0,3,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,6],
+    [8,0,3,3,0,0,0,0,0,8,8,4,0,0,0,4,0,0,0,4,0,0,0,4],
+    [8,0,0,0,0,0,0,0,0,0,8,4,0,0,0,0,0,6,6,6,0,6,4,6],
+    [8,8,8,8,0,8,8,8,8,8,8,4,4,4,4,4,4,6,0,0,0,0,0,6],
+    [7,7,7,7,0,7,7,7,7,0,8,0,8,0,8,0,8,4,0,4,0,6,0,6],
+    [7,7,0,0,0,0,0,0,7,8,0,8,0,8,0,8,8,6,0,0,0,0,0,6],
+    [7,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,8,6,0,0,0,0,0,4],
+    [7,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,8,6,0,6,0,6,0,6],
+    [7,7,0,0,0,0,0,0,7,8,0,8,0,8,0,8,8,6,4,6,0,6,6,6],
+    [7,7,7,7,0,7,7,7,7,8,8,4,0,6,8,4,8,3,3,3,0,3,3,3],
+    [2,2,2,2,0,2,2,2,2,4,6,4,0,0,6,0,6,3,0,0,0,0,0,3],
+    [2,2,0,0,0,0,0,2,2,4,0,0,0,0,0,0,4,3,0,0,0,0,0,3],
+    [2,0,0,0,0,0,0,0,2,4,0,0,0,0,0,0,4,3,0,0,0,0,0,3],
+    [1,0,0,0,0,0,0,0,1,4,4,4,4,4,6,0,6,3,3,0,0,0,3,3],
+    [2,0,0,0,0,0,0,0,2,2,2,1,2,2,2,6,6,0,0,5,0,5,0,5],
+    [2,2,0,0,0,0,0,2,2,2,0,0,0,2,2,0,5,0,5,0,0,0,5,5],
+    [2,0,0,0,0,0,0,0,2,0,0,0,0,0,2,5,0,5,0,5,0,5,0,5],
+    [1,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,5],
+    [2,0,0,0,0,0,0,0,2,0,0,0,0,0,2,5,0,5,0,5,0,5,0,5],
+    [2,2,0,0,0,0,0,2,2,2,0,0,0,2,2,0,5,0,5,0,0,0,5,5],
+    [2,2,2,2,1,2,2,2,2,2,2,1,2,2,2,5,5,5,5,5,5,5,5,5],
+];
+
+/// The floor material of each cell, 0 for the plain floor and otherwise one more than its index
+/// in `FLOOR_MATERIALS`.
+#[rustfmt::skip]
+pub const FLOOR_MAP: [[usize; MAP_WIDTH]; MAP_HEIGHT] = [
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,2,2,0,2,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,2,2,1,1,1,2,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,2,2,1,1,1,2,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,2,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+    [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+];
+
+/// A floor that blends in a reflection of the wall and ceiling above it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FloorMaterial {
+    /// How much of the reflection shows, out of 256.
+    pub reflectivity: u16,
+    /// How far ripples bend the reflection, as a fraction of the row's distance below the
+    /// horizon. 0 keeps it still.
+    pub ripple: f64,
+}
+
+pub const FLOOR_MATERIALS: [FloorMaterial; 2] = [
+    // a pool of water in the small room just below the middle of the map
+    FloorMaterial {
+        reflectivity: 144,
+        ripple: 0.04,
+    },
+    // polished marble around it
+    FloorMaterial {
+        reflectivity: 64,
+        ripple: 0.0,
+    },
+];
+
+/// The material of the floor in cell `x`, `y`, if it isn't the plain floor.
+pub fn floor_material(x: usize, y: usize) -> Option<FloorMaterial> {
+    match FLOOR_MAP.get(x).and_then(|row| row.get(y)) {
+        Some(&material) if material > 0 => Some(FLOOR_MATERIALS[material - 1]),
+        _ => None,
+    }
+}
+
+/// The shape of a wall cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    /// Fills the whole cell.
+    Block,
+    /// Fills the half of the cell on one side of a corner to corner diagonal.
+    Diagonal(Corner),
+    /// A round pillar of the given radius in the middle of the cell.
+    Cylinder(f64),
+}
+
+/// A corner of a cell, named by which ends of the cell's x and y it's at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Corner {
+    MinXMinY,
+    MinXMaxY,
+    MaxXMinY,
+    MaxXMaxY,
+}
+
+/// Wall cells that aren't blocks.
+pub const SHAPES: [(usize, usize, Shape); 8] = [
+    // the corners of the room on the left of the map are cut off
+    (8, 1, Shape::Diagonal(Corner::MinXMinY)),
+    (8, 8, Shape::Diagonal(Corner::MinXMaxY)),
+    (11, 1, Shape::Diagonal(Corner::MaxXMinY)),
+    (11, 8, Shape::Diagonal(Corner::MaxXMaxY)),
+    // round pillars in the room at the top right of the map
+    (2, 15, Shape::Cylinder(PILLAR_RADIUS)),
+    (2, 19, Shape::Cylinder(PILLAR_RADIUS)),
+    (4, 15, Shape::Cylinder(PILLAR_RADIUS)),
+    (4, 19, Shape::Cylinder(PILLAR_RADIUS)),
+];
+
+const PILLAR_RADIUS: f64 = 0.3;
+
+/// The shape of the wall in cell `x`, `y`.
+pub fn shape(x: usize, y: usize) -> Shape {
+    SHAPES
+        .iter()
+        .find(|&&(shape_x, shape_y, _)| (shape_x, shape_y) == (x, y))
+        .map_or(Shape::Block, |&(_, _, shape)| shape)
+}
+
+impl Corner {
+    /// The diagonal through the cell as `(a, b, c)`. A point `x`, `y` from the cell's minimum
+    /// corner is in the solid half when `a * x + b * y < c`, so `(a, b)` points out of it.
+    pub fn edge(self) -> (i32, i32, i32) {
+        match self {
+            Corner::MinXMinY => (1, 1, 1),
+            Corner::MinXMaxY => (1, -1, 0),
+            Corner::MaxXMinY => (-1, 1, 0),
+            Corner::MaxXMaxY => (-1, -1, -1),
+        }
+    }
+}
+
+/// Whether the point `x`, `y` is inside a wall.
+pub fn is_solid(x: f64, y: f64) -> bool {
+    let (map_x, map_y) = (x as usize, y as usize);
+    if WORLD_MAP[map_x][map_y] == 0 {
+        return false;
+    }
+    match shape(map_x, map_y) {
+        Shape::Block => true,
+        Shape::Diagonal(corner) => {
+            let (a, b, c) = corner.edge();
+            a as f64 * (x - map_x as f64) + b as f64 * (y - map_y as f64) < c as f64
+        }
+        Shape::Cylinder(radius) => (x - map_x as f64 - 0.5).hypot(y - map_y as f64 - 0.5) < radius,
+    }
+}
+
+/// A side of a cell, named by which end of the cell's x or y it's at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Face {
+    MinX,
+    MaxX,
+    MinY,
+    MaxY,
+}
+
+/// Going out of `cell` through `face` comes out in `target`, turned counter-clockwise by
+/// `turns` quarter turns. Portals only lead one way, so the way back is a second portal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Portal {
+    pub cell: (usize, usize),
+    pub face: Face,
+    pub target: (usize, usize),
+    pub turns: u8,
+}
+
+pub const PORTALS: [Portal; 2] = [
+    // the top and left walls of the room at the top left of the map lead into each other
+    Portal {
+        cell: (1, 5),
+        face: Face::MinX,
+        target: (5, 1),
+        turns: 3,
+    },
+    Portal {
+        cell: (5, 1),
+        face: Face::MinY,
+        target: (1, 5),
+        turns: 1,
+    },
+];
+
+/// The index in `PORTALS` of the portal on `face` of cell `x`, `y`.
+pub fn portal(x: usize, y: usize, face: Face) -> Option<usize> {
+    PORTALS
+        .iter()
+        .position(|portal| portal.cell == (x, y) && portal.face == face)
+}
+
+impl Portal {
+    /// Where the point `x`, `y` past the portal's face is on the other side.
+    pub fn point<T: Scalar>(&self, x: T, y: T) -> (T, T) {
+        let (from_x, from_y) = self.beyond();
+        let (x, y) = self.turn(x - from_x, y - from_y);
+        let (to_x, to_y) = center(self.target);
+        (x + to_x, y + to_y)
+    }
+
+    /// Where the point `x`, `y` on the other side is seen through the portal, the opposite of
+    /// `point`.
+    pub fn point_back<T: Scalar>(&self, x: T, y: T) -> (T, T) {
+        let (to_x, to_y) = center(self.target);
+        let (mut x, mut y) = (x - to_x, y - to_y);
+        for _ in self.turns % 4..4 {
+            (x, y) = (-y, x);
+        }
+        let (from_x, from_y) = self.beyond();
+        (x + from_x, y + from_y)
+    }
+
+    /// The direction `x`, `y` turned the way going through the portal turns it.
+    pub fn turn<T: Scalar>(&self, mut x: T, mut y: T) -> (T, T) {
+        for _ in 0..self.turns % 4 {
+            (x, y) = (-y, x);
+        }
+        (x, y)
+    }
+
+    /// The middle of the cell on the far side of the portal's face, where `target` takes its
+    /// place.
+    fn beyond<T: Scalar>(&self) -> (T, T) {
+        let (x, y) = self.cell;
+        center(match self.face {
+            Face::MinX => (x - 1, y),
+            Face::MaxX => (x + 1, y),
+            Face::MinY => (x, y - 1),
+            Face::MaxY => (x, y + 1),
+        })
+    }
+}
+
+/// How one face of a wall cell is drawn, instead of with the cell's own texture from
+/// `WORLD_MAP`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FaceTexture {
+    pub cell: (usize, usize),
+    pub face: Face,
+    /// An index into the textures, like `Sprite::texture`.
+    pub texture: usize,
+    /// How far the texture is shifted across and down the face, in whole textures.
+    pub offset: (f64, f64),
+    /// How fast the offset changes, in textures per second.
+    pub scroll: (f64, f64),
+}
+
+pub const FACE_TEXTURES: [FaceTexture; 4] = [
+    // along the hall in the middle of the map, an eagle hangs on the wall between two doorways
+    FaceTexture {
+        cell: (8, 11),
+        face: Face::MaxX,
+        texture: texture("eagle"),
+        offset: (0.0, 0.0),
+        scroll: (0.0, 0.0),
+    },
+    // a wooden conveyor runs past the next doorway
+    FaceTexture {
+        cell: (8, 13),
+        face: Face::MaxX,
+        texture: texture("wood"),
+        offset: (0.0, 0.0),
+        scroll: (0.5, 0.0),
+    },
+    // and the stone across from it is laid half a block over
+    FaceTexture {
+        cell: (11, 13),
+        face: Face::MinX,
+        texture: texture("colorstone"),
+        offset: (0.5, 0.25),
+        scroll: (0.0, 0.0),
+    },
+    // water runs down the far wall of the pool
+    FaceTexture {
+        cell: (14, 16),
+        face: Face::MinY,
+        texture: texture("water"),
+        offset: (0.0, 0.0),
+        scroll: (0.0, 0.0),
+    },
+];
+
+/// How `face` of cell `x`, `y` is drawn, if it isn't with the cell's own texture.
+pub fn face_texture(x: usize, y: usize, face: Face) -> Option<FaceTexture> {
+    FACE_TEXTURES
+        .iter()
+        .find(|texture| texture.cell == (x, y) && texture.face == face)
+        .copied()
+}
+
+/// Decals stamped on when the map loads, which stay put however many are added while playing.
+pub const DECALS: [Decal; 3] = [
+    // a poster of the eagle and what's left of a fight in the room at the bottom right of the
+    // map
+    Decal {
+        cell: (9, 17),
+        face: Face::MaxY,
+        u: 0.5,
+        v: 0.4,
+        size: 0.5,
+        texture: texture("eagle"),
+    },
+    Decal {
+        cell: (9, 17),
+        face: Face::MaxY,
+        u: 0.9,
+        v: 0.3,
+        size: 0.15,
+        texture: texture("bullethole"),
+    },
+    Decal {
+        cell: (8, 17),
+        face: Face::MaxY,
+        u: 0.4,
+        v: 0.55,
+        size: 0.6,
+        texture: texture("blood"),
+    },
+];
+
+// how many portals a ray can go through and how many times it can bounce off mirrors;
+// past these they act like walls, so a ray between two facing each other still ends
+pub const MAX_PORTALS: usize = 8;
+pub const MAX_REFLECTIONS: usize = 4;
+
+/// Where a ray met the face of a block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FaceHit {
+    pub cell: (usize, usize),
+    pub face: Face,
+    /// How far across the face it is, from 0 to 1, left to right as seen from outside.
+    pub u: f64,
+    pub distance: f64,
+}
+
+/// The face of the block that a ray from `x`, `y` along `dir_x`, `dir_y` hits first, or
+/// `None` if it hits a wall that isn't a block. The ray goes through portals and bounces off
+/// mirrors like the rays the scene is drawn with, so it ends on the face seen past them.
+pub fn trace(mut x: f64, mut y: f64, mut dir_x: f64, mut dir_y: f64) -> Option<FaceHit> {
+    let (mut map_x, mut map_y) = (x as usize, y as usize);
+    // how far along the ray the next face across each axis is, never for one it runs along
+    let axis = |pos: f64, map: usize, dir: f64| {
+        let delta_dist = (1.0 / dir).abs();
+        match dir {
+            dir if dir < 0.0 => (-1, (pos - map as f64) * delta_dist, delta_dist),
+            dir if dir > 0.0 => (1, (map as f64 + 1.0 - pos) * delta_dist, delta_dist),
+            _ => (1, f64::INFINITY, f64::INFINITY),
+        }
+    };
+    let (mut step_x, mut side_dist_x, mut delta_dist_x) = axis(x, map_x, dir_x);
+    let (mut step_y, mut side_dist_y, mut delta_dist_y) = axis(y, map_y, dir_y);
+    let (mut portals, mut reflections) = (0, 0);
+
+    loop {
+        // the face the ray leaves the current cell through and the one it enters the next by
+        let (exit, entry, distance) = if side_dist_x < side_dist_y {
+            match step_x {
+                1 => (Face::MaxX, Face::MinX, side_dist_x),
+                _ => (Face::MinX, Face::MaxX, side_dist_x),
+            }
+        } else {
+            match step_y {
+                1 => (Face::MaxY, Face::MinY, side_dist_y),
+                _ => (Face::MinY, Face::MaxY, side_dist_y),
+            }
+        };
+        let through = portal(map_x, map_y, exit).filter(|_| portals < MAX_PORTALS);
+        if let Some(index) = through {
+            let portal = &PORTALS[index];
+            (x, y) = portal.point(x, y);
+            (dir_x, dir_y) = portal.turn(dir_x, dir_y);
+            (map_x, map_y) = portal.target;
+            (step_x, side_dist_x, delta_dist_x) = axis(x, map_x, dir_x);
+            (step_y, side_dist_y, delta_dist_y) = axis(y, map_y, dir_y);
+            portals += 1;
+            continue;
+        }
+
+        let back = (map_x, map_y);
+        if side_dist_x < side_dist_y {
+            map_x = (map_x as i32 + step_x) as usize;
+            side_dist_x += delta_dist_x;
+        } else {
+            map_y = (map_y as i32 + step_y) as usize;
+            side_dist_y += delta_dist_y;
+        }
+        if WORLD_MAP[map_x][map_y] == 0 {
+            continue;
+        }
+        if mirror(map_x, map_y).is_some() && reflections < MAX_REFLECTIONS {
+            // the ray bounces back into the cell in front of the mirror
+            (x, y) = entry.reflect((map_x, map_y), x, y);
+            (dir_x, dir_y) = entry.reflect_dir(dir_x, dir_y);
+            (map_x, map_y) = back;
+            (step_x, side_dist_x, delta_dist_x) = axis(x, map_x, dir_x);
+            (step_y, side_dist_y, delta_dist_y) = axis(y, map_y, dir_y);
+            reflections += 1;
+            continue;
+        }
+        if shape(map_x, map_y) != Shape::Block {
+            return None;
+        }
+
+        let (hit_x, hit_y) = (x + distance * dir_x, y + distance * dir_y);
+        let u = match entry {
+            Face::MinX => 1.0 - hit_y.fract(),
+            Face::MaxX => hit_y.fract(),
+            Face::MinY => hit_x.fract(),
+            Face::MaxY => 1.0 - hit_x.fract(),
+        };
+        return Some(FaceHit {
+            cell: (map_x, map_y),
+            face: entry,
+            u,
+            distance,
+        });
+    }
+}
+
+/// A wall cell that reflects the scene from all of its faces, darkening what's seen in it by
+/// `tint`, out of 256 for each of red, green and blue.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mirror {
+    pub cell: (usize, usize),
+    pub tint: [u16; 3],
+}
+
+pub const MIRRORS: [Mirror; 3] = [
+    // the wall at the end of the long corridor, a plain mirror between two sheets of polished
+    // steel
+    Mirror {
+        cell: (19, 0),
+        tint: [176, 184, 200],
+    },
+    Mirror {
+        cell: (20, 0),
+        tint: [232, 232, 232],
+    },
+    Mirror {
+        cell: (21, 0),
+        tint: [176, 184, 200],
+    },
+];
+
+/// The index in `MIRRORS` of the mirror in cell `x`, `y`.
+pub fn mirror(x: usize, y: usize) -> Option<usize> {
+    MIRRORS.iter().position(|mirror| mirror.cell == (x, y))
+}
+
+impl Face {
+    pub const ALL: [Face; 4] = [Face::MinX, Face::MaxX, Face::MinY, Face::MaxY];
+
+    /// The point `x`, `y` reflected in this face of `cell`.
+    pub fn reflect<T: Scalar>(self, (cell_x, cell_y): (usize, usize), x: T, y: T) -> (T, T) {
+        let (cell_x, cell_y) = (T::from_i32(cell_x as i32), T::from_i32(cell_y as i32));
+        let two = T::from_i32(2);
+        let one = T::from_i32(1);
+        match self {
+            Face::MinX => (two * cell_x - x, y),
+            Face::MaxX => (two * (cell_x + one) - x, y),
+            Face::MinY => (x, two * cell_y - y),
+            Face::MaxY => (x, two * (cell_y + one) - y),
+        }
+    }
+
+    /// The direction `x`, `y` reflected in this face.
+    pub fn reflect_dir<T: Scalar>(self, x: T, y: T) -> (T, T) {
+        match self {
+            Face::MinX | Face::MaxX => (-x, y),
+            Face::MinY | Face::MaxY => (x, -y),
+        }
+    }
+}
+
+fn center<T: Scalar>((x, y): (usize, usize)) -> (T, T) {
+    let half = T::from_f64(0.5);
+    (T::from_i32(x as i32) + half, T::from_i32(y as i32) + half)
+}
@@ -0,0 +1,276 @@
+use std::fs;
+use std::path::Path;
+
+// the 4x4 Bayer matrix, thresholds out of 16
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// One step of the post-processing chain.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pass {
+    /// Darkens every other row by `strength`, from 0 to 1.
+    Scanlines { strength: f64 },
+    /// Bulges the frame out like the glass of a CRT. The corners are pushed out of the frame
+    /// by `amount` and go black.
+    Curvature { amount: f64 },
+    /// Darkens toward the corners, which are `strength` darker than the middle.
+    Vignette { strength: f64 },
+    /// Cuts each channel down to `bits` of precision, hiding the banding with ordered dithering.
+    Dither { bits: u32 },
+    /// Grades the colors through a 3D lookup table.
+    Lut(Lut),
+}
+
+/// A 3D color lookup table of `size` entries along each side, indexed red first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut {
+    size: usize,
+    table: Vec<[f32; 3]>,
+}
+
+/// Passes run over the finished frame, in order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PostProcess {
+    passes: Vec<Pass>,
+}
+
+impl PostProcess {
+    pub fn new(passes: Vec<Pass>) -> PostProcess {
+        PostProcess { passes }
+    }
+
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    /// Reads a chain from a file, see `parse`.
+    pub fn load(path: impl AsRef<Path>) -> Result<PostProcess, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("{:?}", e))?;
+        PostProcess::parse(&text, path.parent().unwrap_or_else(|| Path::new("")))
+    }
+
+    /// Parses a chain with one pass per line: `scanlines <strength>`, `curvature <amount>`,
+    /// `vignette <strength>`, `dither <bits>` or `lut <file.cube>`. LUT files are found relative
+    /// to `directory`. Blank lines and lines starting with `#` are skipped.
+    pub fn parse(text: &str, directory: &Path) -> Result<PostProcess, String> {
+        let mut passes = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            let (name, value) = match line.split_once(char::is_whitespace) {
+                Some((name, value)) => (name, value.trim()),
+                None => return Err(error("expected <pass> <value>")),
+            };
+            let amount = || {
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|amount| (0.0..=1.0).contains(amount))
+                    .ok_or_else(|| error("expected an amount from 0 to 1"))
+            };
+            passes.push(match name {
+                "scanlines" => Pass::Scanlines {
+                    strength: amount()?,
+                },
+                "curvature" => Pass::Curvature { amount: amount()? },
+                "vignette" => Pass::Vignette {
+                    strength: amount()?,
+                },
+                "dither" => Pass::Dither {
+                    bits: value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|bits| (1..=8).contains(bits))
+                        .ok_or_else(|| error("expected 1 to 8 bits"))?,
+                },
+                "lut" => {
+                    let text = fs::read_to_string(directory.join(value))
+                        .map_err(|e| error(&format!("{:?}", e)))?;
+                    Pass::Lut(Lut::parse(&text).map_err(|e| error(&e))?)
+                }
+                _ => return Err(error(&format!("unknown pass {}", name))),
+            });
+        }
+
+        Ok(PostProcess { passes })
+    }
+
+    /// Runs every pass over `frame`, an RGB24 frame of `width * height` pixels. `scratch` holds
+    /// a copy of the frame for the passes that move pixels around.
+    pub fn apply(&self, frame: &mut [u8], width: usize, height: usize, scratch: &mut Vec<u8>) {
+        for pass in self.passes.iter() {
+            match pass {
+                Pass::Scanlines { strength } => {
+                    let scale = 1.0 - strength;
+                    for row in frame.chunks_exact_mut(width * 3).skip(1).step_by(2) {
+                        for channel in row.iter_mut() {
+                            *channel = (*channel as f64 * scale).round() as u8;
+                        }
+                    }
+                }
+                Pass::Curvature { amount } => {
+                    scratch.clear();
+                    scratch.extend_from_slice(frame);
+                    for (y, row) in frame.chunks_exact_mut(width * 3).enumerate() {
+                        let ny = centered(y, height);
+                        for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+                            let nx = centered(x, width);
+                            // further out points are pushed out more
+                            let scale = 1.0 + amount * (nx * nx + ny * ny);
+                            let (sx, sy) = (nx * scale, ny * scale);
+                            if sx.abs() > 1.0 || sy.abs() > 1.0 {
+                                pixel.copy_from_slice(&[0; 3]);
+                                continue;
+                            }
+                            let source_x =
+                                (((sx + 1.0) * width as f64 / 2.0) as usize).min(width - 1);
+                            let source_y =
+                                (((sy + 1.0) * height as f64 / 2.0) as usize).min(height - 1);
+                            let start = (source_y * width + source_x) * 3;
+                            pixel.copy_from_slice(&scratch[start..start + 3]);
+                        }
+                    }
+                }
+                Pass::Vignette { strength } => {
+                    for (y, row) in frame.chunks_exact_mut(width * 3).enumerate() {
+                        let ny = centered(y, height);
+                        for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+                            let nx = centered(x, width);
+                            // the corners are at a squared distance of 2
+                            let scale = 1.0 - strength * (nx * nx + ny * ny) / 2.0;
+                            for channel in pixel.iter_mut() {
+                                *channel = (*channel as f64 * scale).round() as u8;
+                            }
+                        }
+                    }
+                }
+                Pass::Dither { bits } => {
+                    let levels = ((1 << bits) - 1) as f64;
+                    for (y, row) in frame.chunks_exact_mut(width * 3).enumerate() {
+                        for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+                            let threshold = (BAYER[y % 4][x % 4] as f64 + 0.5) / 16.0 - 0.5;
+                            for channel in pixel.iter_mut() {
+                                let level = (*channel as f64 / 255.0 * levels + threshold)
+                                    .round()
+                                    .clamp(0.0, levels);
+                                *channel = (level / levels * 255.0).round() as u8;
+                            }
+                        }
+                    }
+                }
+                Pass::Lut(lut) => {
+                    for pixel in frame.chunks_exact_mut(3) {
+                        let color = lut.sample([pixel[0], pixel[1], pixel[2]]);
+                        pixel.copy_from_slice(&color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Where pixel `index` of `size` sits between -1 and 1, through the middle of the pixel.
+fn centered(index: usize, size: usize) -> f64 {
+    (index as f64 + 0.5) / size as f64 * 2.0 - 1.0
+}
+
+impl Lut {
+    /// Parses a `.cube` file. Only 3D tables over the default 0 to 1 domain are supported.
+    pub fn parse(text: &str) -> Result<Lut, String> {
+        let mut size = None;
+        let mut table = Vec::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("TITLE") {
+                continue;
+            }
+            if let Some(value) = line.strip_prefix("LUT_3D_SIZE") {
+                size = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&size| size >= 2)
+                        .ok_or(format!("bad LUT_3D_SIZE: {}", value.trim()))?,
+                );
+                continue;
+            }
+            if line.starts_with("DOMAIN_MIN") || line.starts_with("DOMAIN_MAX") {
+                let default = if line.starts_with("DOMAIN_MIN") {
+                    0.0
+                } else {
+                    1.0
+                };
+                let values: Vec<f64> = line
+                    .split_whitespace()
+                    .skip(1)
+                    .filter_map(|value| value.parse().ok())
+                    .collect();
+                if values != [default; 3] {
+                    return Err(format!("only the default domain is supported: {}", line));
+                }
+                continue;
+            }
+
+            let values: Vec<f32> = line
+                .split_whitespace()
+                .map(|value| value.parse::<f32>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("expected <r> <g> <b> but got {}", line))?;
+            match values[..] {
+                [red, green, blue] => table.push([red, green, blue]),
+                _ => return Err(format!("expected <r> <g> <b> but got {}", line)),
+            }
+        }
+
+        let size = size.ok_or("missing LUT_3D_SIZE")?;
+        if table.len() != size * size * size {
+            return Err(format!(
+                "expected {} entries but got {}",
+                size * size * size,
+                table.len()
+            ));
+        }
+        Ok(Lut { size, table })
+    }
+
+    /// Looks `color` up, blending the eight entries around it.
+    fn sample(&self, color: [u8; 3]) -> [u8; 3] {
+        let last = (self.size - 1) as f32;
+        let mut low = [0; 3];
+        let mut fraction = [0.0; 3];
+        for i in 0..3 {
+            let position = color[i] as f32 / 255.0 * last;
+            low[i] = (position as usize).min(self.size - 2);
+            fraction[i] = position - low[i] as f32;
+        }
+
+        let mut result = [0.0; 3];
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = 0;
+            // red changes fastest, then green, then blue
+            for i in (0..3).rev() {
+                let high = corner >> i & 1;
+                weight *= if high == 1 {
+                    fraction[i]
+                } else {
+                    1.0 - fraction[i]
+                };
+                index = index * self.size + low[i] + high;
+            }
+            for (channel, value) in result.iter_mut().zip(self.table[index]) {
+                *channel += weight * value;
+            }
+        }
+        [
+            (result[0] * 255.0).round().clamp(0.0, 255.0) as u8,
+            (result[1] * 255.0).round().clamp(0.0, 255.0) as u8,
+            (result[2] * 255.0).round().clamp(0.0, 255.0) as u8,
+        ]
+    }
+}
@@ -0,0 +1,1633 @@
+use crate::camera::Camera;
+use crate::decal::Decals;
+use crate::fixed::{Fixed, Scalar};
+use crate::map::{
+    face_texture, floor_material, mirror, portal, shape, Corner, Face, Shape, DECALS, MAX_PORTALS,
+    MAX_REFLECTIONS, MIRRORS, PORTALS, WORLD_MAP,
+};
+use crate::postprocess::PostProcess;
+use crate::span::{simd_available, FloorSpan};
+use crate::sprite::{sort_sprites, Sprite};
+use crate::stereo::{anaglyph, place_eye, Stereo};
+use crate::supersample::{downsample, Downsample};
+use crate::texture::{
+    current_frame, mip_level, texture, Filtering, Texture, TEXTURE_HEIGHT, TEXTURE_WIDTH,
+};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::f64::consts::PI;
+use std::mem;
+use std::ops::Range;
+use std::thread;
+
+pub const SCREEN_WIDTH: usize = 640;
+pub const SCREEN_HEIGHT: usize = 480;
+
+const FLOOR_TEXTURE: usize = texture("greystone");
+const CEILING_TEXTURE: usize = texture("wood");
+// how far apart the eyes of a stereo pair are, in map cells
+const EYE_SEPARATION: f64 = 0.1;
+// a ray's first leg and one more for each portal or mirror it carries on through
+const MAX_LEGS: usize = MAX_PORTALS + MAX_REFLECTIONS + 1;
+// ripples on reflective floors, in waves per map cell and waves per second
+const RIPPLE_DENSITY: f64 = 1.5;
+const RIPPLE_SPEED: f64 = 0.8;
+
+pub struct Renderer {
+    width: usize,
+    height: usize,
+    threads: usize,
+    // the workers the strips and bands of each frame are handed to, kept for as long as the
+    // renderer so no threads are started per frame
+    pool: Option<ThreadPool>,
+    mipmapping: bool,
+    filtering: Filtering,
+    supersampling: usize,
+    downsample: Downsample,
+    fixed_point: bool,
+    simd: bool,
+    post_process: PostProcess,
+    post_processing: bool,
+    stereo: Stereo,
+    time: f64,
+    decals: Decals,
+
+    // 1D Zbuffer
+    z_buffer: Vec<f64>,
+    // the legs of each column's ray, past the portals and mirrors it went through
+    column_legs: Vec<ColumnLegs>,
+
+    // the ways the columns see the map, directly and past portals and mirrors
+    transforms: Vec<Transform>,
+    // every sprite where it's seen each of those ways
+    seen_sprites: Vec<SeenSprite>,
+
+    // arrays used to sort the sprites
+    sprite_order: Vec<usize>,
+    sprite_distance: Vec<f64>,
+
+    // the floor seen on each row below the horizon
+    floor_rows: FloorRows<f64>,
+    fixed_floor_rows: FloorRows<Fixed>,
+    // where the floor texel under each row starts, a column's worth for each strip
+    floor_texels: Vec<usize>,
+
+    // the strips of columns a frame is split into across threads, each stored row by row
+    strips: Vec<u8>,
+
+    // the full size frame before it's downsampled
+    supersampled: Vec<u8>,
+
+    // a copy of the frame for the post-processing passes that move pixels
+    post_scratch: Vec<u8>,
+
+    // the eye of a stereo pair that's rendered apart from the frame
+    eye_frame: Vec<u8>,
+}
+
+/// The camera and screen values every casting pass needs, worked out once per frame.
+#[derive(Clone, Copy)]
+struct View<T> {
+    pos_x: T,
+    pos_y: T,
+    dir_x: T,
+    dir_y: T,
+    plane_x: T,
+    plane_y: T,
+    width: usize,
+    height: usize,
+    projection: T,
+    mipmapping: bool,
+    filtering: Filtering,
+    simd: bool,
+    time: f64,
+}
+
+/// How far away the floor seen on each row below the horizon is, and the mip level it's drawn
+/// with. They're kept in separate arrays so a run of rows can be loaded at once.
+#[derive(Default)]
+struct FloorRows<T> {
+    distances: Vec<T>,
+    mip_levels: Vec<usize>,
+}
+
+impl Renderer {
+    pub fn new(width: usize, height: usize) -> Renderer {
+        let threads = thread::available_parallelism()
+            .map(|threads| threads.get())
+            .unwrap_or(1);
+        Renderer {
+            width,
+            height,
+            threads,
+            pool: worker_pool(threads),
+            mipmapping: true,
+            filtering: Filtering::Nearest,
+            supersampling: 1,
+            downsample: Downsample::Box,
+            fixed_point: false,
+            simd: simd_available(),
+            post_process: PostProcess::default(),
+            post_processing: true,
+            stereo: Stereo::Off,
+            time: 0.0,
+            decals: Decals::default(),
+            z_buffer: vec![0.0; width],
+            column_legs: Vec::new(),
+            transforms: Vec::new(),
+            seen_sprites: Vec::new(),
+            sprite_order: Vec::new(),
+            sprite_distance: Vec::new(),
+            floor_rows: FloorRows::default(),
+            fixed_floor_rows: FloorRows::default(),
+            floor_texels: Vec::new(),
+            strips: Vec::new(),
+            supersampled: Vec::new(),
+            post_scratch: Vec::new(),
+            eye_frame: Vec::new(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Changes the size of the frames rendered from now on.
+    pub fn set_size(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Sets how many threads a frame is split across. The output is the same for any count,
+    /// a single thread renders without spawning any workers.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+        self.pool = worker_pool(self.threads);
+    }
+
+    pub fn mipmapping(&self) -> bool {
+        self.mipmapping
+    }
+
+    /// Turns mipmapping of walls and floors on or off. Off gives the crisp, shimmering look of
+    /// the original.
+    pub fn set_mipmapping(&mut self, mipmapping: bool) {
+        self.mipmapping = mipmapping;
+    }
+
+    pub fn filtering(&self) -> Filtering {
+        self.filtering
+    }
+
+    /// Sets how walls, floors and sprites are sampled.
+    pub fn set_filtering(&mut self, filtering: Filtering) {
+        self.filtering = filtering;
+    }
+
+    pub fn supersampling(&self) -> usize {
+        self.supersampling
+    }
+
+    /// Renders `factor` times the width and height and downsamples the result, smoothing the
+    /// edges of walls and sprites. A factor of 1 turns it off.
+    pub fn set_supersampling(&mut self, factor: usize) {
+        self.supersampling = factor.max(1);
+    }
+
+    pub fn downsample(&self) -> Downsample {
+        self.downsample
+    }
+
+    /// Sets the filter a supersampled frame is downsampled with.
+    pub fn set_downsample(&mut self, downsample: Downsample) {
+        self.downsample = downsample;
+    }
+
+    pub fn fixed_point(&self) -> bool {
+        self.fixed_point
+    }
+
+    /// Switches wall, floor and sprite casting to 16.16 fixed-point math, which gives the same
+    /// frame on every machine. It lands within a pixel of the floating-point frame.
+    pub fn set_fixed_point(&mut self, fixed_point: bool) {
+        self.fixed_point = fixed_point;
+    }
+
+    pub fn simd(&self) -> bool {
+        self.simd
+    }
+
+    /// Turns the vectorized floor and ceiling texel lookup on or off. It's only used when the
+    /// CPU supports it and the frame is cast in floating point, and gives the same frame as the
+    /// scalar one.
+    pub fn set_simd(&mut self, simd: bool) {
+        self.simd = simd;
+    }
+
+    pub fn post_process(&self) -> &PostProcess {
+        &self.post_process
+    }
+
+    /// Sets the passes run over each finished frame.
+    pub fn set_post_process(&mut self, post_process: PostProcess) {
+        self.post_process = post_process;
+    }
+
+    pub fn post_processing(&self) -> bool {
+        self.post_processing
+    }
+
+    /// Turns the post-processing passes on or off, keeping them for later.
+    pub fn set_post_processing(&mut self, post_processing: bool) {
+        self.post_processing = post_processing;
+    }
+
+    pub fn stereo(&self) -> Stereo {
+        self.stereo
+    }
+
+    /// Renders the scene once for each eye, from either side of the camera, and combines them.
+    pub fn set_stereo(&mut self, stereo: Stereo) {
+        self.stereo = stereo;
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Sets the time in seconds that animated surfaces, like rippling water, are drawn at.
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    /// The decals stamped on while playing, on top of the ones the map starts with.
+    pub fn decals(&self) -> &Decals {
+        &self.decals
+    }
+
+    pub fn decals_mut(&mut self) -> &mut Decals {
+        &mut self.decals
+    }
+
+    /// Renders one frame as seen from `camera` into `texture_buffer`, an RGB24 buffer of
+    /// `width * height` pixels.
+    pub fn render(
+        &mut self,
+        camera: &Camera,
+        textures: &[Texture],
+        sprites: &[Sprite],
+        texture_buffer: &mut [u8],
+    ) {
+        let (width, height) = (self.width, self.height);
+        let left = camera.eye(-EYE_SEPARATION / 2.0);
+        let right = camera.eye(EYE_SEPARATION / 2.0);
+        let mut eye_frame = mem::take(&mut self.eye_frame);
+        match self.stereo {
+            Stereo::Off => {
+                self.render_scene(camera, textures, sprites, width, height, texture_buffer)
+            }
+            Stereo::Anaglyph => {
+                self.render_scene(&left, textures, sprites, width, height, texture_buffer);
+                eye_frame.resize(width * height * 3, 0);
+                self.render_scene(&right, textures, sprites, width, height, &mut eye_frame);
+                anaglyph(texture_buffer, &eye_frame);
+            }
+            Stereo::SideBySide => {
+                // each eye sees its own half of the frame
+                let left_width = width / 2;
+                for (mut eye, eye_width, first_column) in [
+                    (left, left_width, 0),
+                    (right, width - left_width, left_width),
+                ] {
+                    // a frame a single pixel wide has room for only the right eye
+                    if eye_width == 0 {
+                        continue;
+                    }
+                    eye.set_aspect_ratio(eye_width, height);
+                    eye_frame.resize(eye_width * height * 3, 0);
+                    self.render_scene(&eye, textures, sprites, eye_width, height, &mut eye_frame);
+                    place_eye(texture_buffer, width, &eye_frame, eye_width, first_column);
+                }
+            }
+        }
+        self.eye_frame = eye_frame;
+
+        if self.post_processing {
+            self.post_process.apply(
+                texture_buffer,
+                self.width,
+                self.height,
+                &mut self.post_scratch,
+            );
+        }
+    }
+
+    /// Renders a `width` by `height` view from `camera` before post-processing, supersampled
+    /// when that's on.
+    fn render_scene(
+        &mut self,
+        camera: &Camera,
+        textures: &[Texture],
+        sprites: &[Sprite],
+        width: usize,
+        height: usize,
+        texture_buffer: &mut [u8],
+    ) {
+        let factor = self.supersampling;
+        if factor == 1 {
+            self.cast(camera, textures, sprites, width, height, texture_buffer);
+            return;
+        }
+
+        let mut supersampled = mem::take(&mut self.supersampled);
+        supersampled.resize(width * factor * height * factor * 3, 0);
+        self.cast(
+            camera,
+            textures,
+            sprites,
+            width * factor,
+            height * factor,
+            &mut supersampled,
+        );
+
+        let filter = self.downsample;
+        let source: &[u8] = &supersampled;
+        match &self.pool {
+            None => downsample(source, width, height, factor, filter, 0, texture_buffer),
+            Some(pool) => {
+                // a frame too small to split still needs chunks of at least one byte
+                let band_height = height.div_ceil(self.threads).max(1);
+                pool.scope(|scope| {
+                    for (band, rows) in texture_buffer
+                        .chunks_mut((band_height * width * 3).max(1))
+                        .enumerate()
+                    {
+                        let first_row = band * band_height;
+                        scope.spawn(move |_| {
+                            downsample(source, width, height, factor, filter, first_row, rows)
+                        });
+                    }
+                });
+            }
+        }
+        self.supersampled = supersampled;
+    }
+
+    /// Casts a `width` by `height` frame into `texture_buffer`.
+    fn cast(
+        &mut self,
+        camera: &Camera,
+        textures: &[Texture],
+        sprites: &[Sprite],
+        width: usize,
+        height: usize,
+        texture_buffer: &mut [u8],
+    ) {
+        if self.fixed_point {
+            let mut floor_rows = mem::take(&mut self.fixed_floor_rows);
+            let view = self.view::<Fixed>(camera, width, height);
+            self.cast_with(view, textures, sprites, &mut floor_rows, texture_buffer);
+            self.fixed_floor_rows = floor_rows;
+        } else {
+            let mut floor_rows = mem::take(&mut self.floor_rows);
+            let view = self.view::<f64>(camera, width, height);
+            self.cast_with(view, textures, sprites, &mut floor_rows, texture_buffer);
+            self.floor_rows = floor_rows;
+        }
+    }
+
+    /// Works out the view from `camera` in the number type the frame is cast with.
+    fn view<T: Scalar>(&self, camera: &Camera, width: usize, height: usize) -> View<T> {
+        let Camera {
+            pos_x,
+            pos_y,
+            dir_x,
+            dir_y,
+            plane_x,
+            plane_y,
+            ..
+        } = *camera;
+        let (pos_x, pos_y) = (T::from_f64(pos_x), T::from_f64(pos_y));
+        let (dir_x, dir_y) = (T::from_f64(dir_x), T::from_f64(dir_y));
+        let (plane_x, plane_y) = (T::from_f64(plane_x), T::from_f64(plane_y));
+
+        // pixels per world unit at distance 1, the same horizontally and vertically so the
+        // field of view never stretches walls, floors or sprites
+        let plane_length = plane_x.hypot(plane_y) / dir_x.hypot(dir_y);
+        View {
+            pos_x,
+            pos_y,
+            dir_x,
+            dir_y,
+            plane_x,
+            plane_y,
+            width,
+            height,
+            projection: T::from_i32(width as i32) / (T::from_i32(2) * plane_length),
+            mipmapping: self.mipmapping,
+            filtering: self.filtering,
+            simd: self.simd,
+            time: self.time,
+        }
+    }
+
+    /// Casts the frame using `view`'s number type, keeping its floor rows in `floor_rows`.
+    fn cast_with<T: FloorSpan>(
+        &mut self,
+        view: View<T>,
+        textures: &[Texture],
+        sprites: &[Sprite],
+        floor_rows: &mut FloorRows<T>,
+        texture_buffer: &mut [u8],
+    ) {
+        let View { width, height, .. } = view;
+
+        let horizon = height / 2;
+        floor_rows.distances.resize(height, T::default());
+        floor_rows.mip_levels.resize(height, 0);
+        for y in horizon + 1..height {
+            floor_rows.distances[y] =
+                T::from_f64(0.5) * view.projection / T::from_i32((y - horizon) as i32);
+            // one pixel covers distance / projection world units of floor, which comes out to
+            // half a unit over the rows below the horizon
+            if self.mipmapping {
+                let texels = (TEXTURE_WIDTH / 2) as f64 / (y - horizon) as f64;
+                floor_rows.mip_levels[y] = mip_level(texels);
+            }
+        }
+        let floor_rows: &FloorRows<T> = floor_rows;
+
+        self.z_buffer.resize(width, 0.0);
+        self.column_legs.resize(width, ColumnLegs::default());
+        self.floor_texels.resize(self.threads * height, 0);
+        // split into strips of columns, each drawn into its own buffer and copied into the
+        // frame afterwards in bands of rows, none of them empty even when the frame is
+        // narrower or shorter than the thread count
+        let strip_width = width.div_ceil(self.threads).max(1);
+        if self.pool.is_some() {
+            self.strips.resize(width * height * 3, 0);
+        }
+        let strips = &mut self.strips;
+
+        // walls first, since which sprites show depends on the portals and mirrors the
+        // columns see through
+        let z_buffer = &mut self.z_buffer;
+        let column_legs = &mut self.column_legs;
+        let floor_texels = &mut self.floor_texels;
+        let decals = &self.decals;
+        match &self.pool {
+            None => view.cast_columns(
+                textures,
+                decals,
+                0..width,
+                texture_buffer,
+                z_buffer,
+                column_legs,
+                floor_rows,
+                &mut floor_texels[..height],
+            ),
+            Some(pool) => pool.scope(|scope| {
+                for (strip, (((pixels, z_buffer), column_legs), texels)) in strips
+                    .chunks_mut((strip_width * height * 3).max(1))
+                    .zip(z_buffer.chunks_mut(strip_width))
+                    .zip(column_legs.chunks_mut(strip_width))
+                    .zip(floor_texels.chunks_mut(height.max(1)))
+                    .enumerate()
+                {
+                    let columns = strip * strip_width..strip * strip_width + z_buffer.len();
+                    scope.spawn(move |_| {
+                        view.cast_columns(
+                            textures,
+                            decals,
+                            columns,
+                            pixels,
+                            z_buffer,
+                            column_legs,
+                            floor_rows,
+                            texels,
+                        )
+                    });
+                }
+            }),
+        }
+
+        self.gather_sprites(&view, sprites);
+        let strips = &mut self.strips;
+        let sprites = &self.seen_sprites;
+        let sprite_order = &self.sprite_order;
+        let z_buffer = &self.z_buffer;
+        let column_legs = &self.column_legs;
+        let pool = match &self.pool {
+            Some(pool) => pool,
+            None => {
+                view.cast_sprites(
+                    textures,
+                    sprites,
+                    sprite_order,
+                    0..width,
+                    texture_buffer,
+                    z_buffer,
+                    column_legs,
+                );
+                return;
+            }
+        };
+        pool.scope(|scope| {
+            for (strip, ((pixels, z_buffer), column_legs)) in strips
+                .chunks_mut((strip_width * height * 3).max(1))
+                .zip(z_buffer.chunks(strip_width))
+                .zip(column_legs.chunks(strip_width))
+                .enumerate()
+            {
+                let columns = strip * strip_width..strip * strip_width + z_buffer.len();
+                scope.spawn(move |_| {
+                    view.cast_sprites(
+                        textures,
+                        sprites,
+                        sprite_order,
+                        columns,
+                        pixels,
+                        z_buffer,
+                        column_legs,
+                    )
+                });
+            }
+        });
+
+        let strips: &[u8] = strips;
+        let band_height = height.div_ceil(self.threads).max(1);
+        pool.scope(|scope| {
+            for (band, rows) in texture_buffer
+                .chunks_mut((band_height * width * 3).max(1))
+                .enumerate()
+            {
+                let first_row = band * band_height;
+                scope.spawn(move |_| {
+                    join_strips(strips, width, height, strip_width, first_row, rows)
+                });
+            }
+        });
+    }
+
+    /// Lists every sprite where the camera sees it each way the columns of the frame see the
+    /// map, directly or past portals and mirrors, and sorts them furthest first.
+    fn gather_sprites<T: Scalar>(&mut self, view: &View<T>, sprites: &[Sprite]) {
+        let (pos_x, pos_y) = (view.pos_x, view.pos_y);
+
+        self.transforms.clear();
+        for column in &self.column_legs[..view.width] {
+            for leg in column.legs() {
+                if !self.transforms.contains(&leg.transform) {
+                    self.transforms.push(leg.transform);
+                }
+            }
+        }
+
+        self.seen_sprites.clear();
+        for &transform in &self.transforms {
+            // where the camera is on the sprites' side of the portals and mirrors, which picks
+            // the side of a sprite with rotations that shows
+            let (camera_x, camera_y) = transform.point(pos_x, pos_y);
+            for sprite in sprites.iter() {
+                let (x, y) = transform.point_back(sprite.x, sprite.y);
+                self.seen_sprites.push(SeenSprite {
+                    x,
+                    y,
+                    texture: sprite.texture_from(camera_x, camera_y),
+                    scale_x: sprite.scale_x,
+                    scale_y: sprite.scale_y,
+                    v_move: sprite.v_move,
+                    transform,
+                });
+            }
+        }
+
+        self.sprite_order.clear();
+        self.sprite_distance.clear();
+        for (i, sprite) in self.seen_sprites.iter().enumerate() {
+            self.sprite_order.push(i);
+            let distance_x = pos_x - T::from_f64(sprite.x);
+            let distance_y = pos_y - T::from_f64(sprite.y);
+            self.sprite_distance
+                .push((distance_x * distance_x + distance_y * distance_y).to_f64());
+        }
+        sort_sprites(
+            &mut self.sprite_order,
+            &mut self.sprite_distance,
+            self.seen_sprites.len(),
+        );
+    }
+}
+
+/// Starts `threads` long-lived workers to render on, or none when a frame is rendered on a
+/// single thread. Without them, or if they can't be started, frames are rendered on the
+/// calling thread.
+fn worker_pool(threads: usize) -> Option<ThreadPool> {
+    if threads == 1 {
+        return None;
+    }
+    ThreadPoolBuilder::new().num_threads(threads).build().ok()
+}
+
+/// A ray being followed through the map a cell at a time, using the DDA algorithm. After it
+/// goes through a portal or bounces off a mirror it starts over from where its origin would be
+/// on the other side.
+#[derive(Clone, Copy)]
+struct Ray<T> {
+    pos_x: T,
+    pos_y: T,
+    dir_x: T,
+    dir_y: T,
+    map_x: usize,
+    map_y: usize,
+    step_x: i32,
+    step_y: i32,
+    delta_dist_x: T,
+    delta_dist_y: T,
+    side_dist_x: T,
+    side_dist_y: T,
+}
+
+impl<T: Scalar> Ray<T> {
+    /// A ray from `pos` along `dir` that has reached cell `map`.
+    fn new(
+        (pos_x, pos_y): (T, T),
+        (dir_x, dir_y): (T, T),
+        (map_x, map_y): (usize, usize),
+    ) -> Ray<T> {
+        let zero = T::from_i32(0);
+        let one = T::from_i32(1);
+        let delta_dist_x = if dir_y == zero {
+            zero
+        } else if dir_x == zero {
+            one
+        } else {
+            (one / dir_x).abs()
+        };
+        let delta_dist_y = if dir_x == zero {
+            zero
+        } else if dir_y == zero {
+            one
+        } else {
+            (one / dir_y).abs()
+        };
+
+        let step_x;
+        let side_dist_x = if dir_x < zero {
+            step_x = -1;
+            (pos_x - T::from_i32(map_x as i32)) * delta_dist_x
+        } else {
+            step_x = 1;
+            (T::from_i32(map_x as i32) + one - pos_x) * delta_dist_x
+        };
+
+        let step_y;
+        let side_dist_y = if dir_y < zero {
+            step_y = -1;
+            (pos_y - T::from_i32(map_y as i32)) * delta_dist_y
+        } else {
+            step_y = 1;
+            (T::from_i32(map_y as i32) + one - pos_y) * delta_dist_y
+        };
+
+        Ray {
+            pos_x,
+            pos_y,
+            dir_x,
+            dir_y,
+            map_x,
+            map_y,
+            step_x,
+            step_y,
+            delta_dist_x,
+            delta_dist_y,
+            side_dist_x,
+            side_dist_y,
+        }
+    }
+
+    /// The distance to the face of the current cell the ray came in through, on side 0 for a
+    /// face across x and 1 for one across y.
+    fn face_distance(&self, side: i32) -> T {
+        if side == 0 {
+            (T::from_i32(self.map_x as i32) - self.pos_x + T::from_i32((1 - self.step_x) / 2))
+                / self.dir_x
+        } else {
+            (T::from_i32(self.map_y as i32) - self.pos_y + T::from_i32((1 - self.step_y) / 2))
+                / self.dir_y
+        }
+    }
+
+    /// The face of the current cell the ray came in through, on `side` like `face_distance`.
+    fn entry_face(&self, side: i32) -> Face {
+        match (side, self.step_x, self.step_y) {
+            (0, 1, _) => Face::MinX,
+            (0, _, _) => Face::MaxX,
+            (_, _, 1) => Face::MinY,
+            _ => Face::MaxY,
+        }
+    }
+
+    /// The distance to where the ray leaves the current cell.
+    fn exit(&self) -> T {
+        if self.side_dist_x < self.side_dist_y {
+            self.side_dist_x
+        } else {
+            self.side_dist_y
+        }
+    }
+}
+
+/// Where a point the camera sees is on the map, after the portals and mirrors a leg of a ray
+/// went through: the point `x`, `y` is at `origin + x * axis_x + y * axis_y`. Portals and
+/// mirrors only turn by quarter turns and flip, and move by half cells, so two legs that see
+/// the map the same way have exactly the same transform however they got there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Transform {
+    origin: (f64, f64),
+    axis_x: (i32, i32),
+    axis_y: (i32, i32),
+}
+
+impl Transform {
+    const IDENTITY: Transform = Transform {
+        origin: (0.0, 0.0),
+        axis_x: (1, 0),
+        axis_y: (0, 1),
+    };
+
+    /// This transform followed by going through a portal or mirror, which moves points with
+    /// `point` and turns directions with `turn`.
+    fn then(
+        self,
+        point: impl Fn(f64, f64) -> (f64, f64),
+        turn: impl Fn(f64, f64) -> (f64, f64),
+    ) -> Transform {
+        let axis = |(x, y): (i32, i32)| {
+            let (x, y) = turn(x as f64, y as f64);
+            (x as i32, y as i32)
+        };
+        Transform {
+            origin: point(self.origin.0, self.origin.1),
+            axis_x: axis(self.axis_x),
+            axis_y: axis(self.axis_y),
+        }
+    }
+
+    /// Where the point `x`, `y` the camera sees is on the map.
+    fn point<T: Scalar>(self, x: T, y: T) -> (T, T) {
+        let (origin_x, origin_y) = (T::from_f64(self.origin.0), T::from_f64(self.origin.1));
+        let ((xx, xy), (yx, yy)) = (self.axis_x, self.axis_y);
+        (
+            origin_x + x * T::from_i32(xx) + y * T::from_i32(yx),
+            origin_y + x * T::from_i32(xy) + y * T::from_i32(yy),
+        )
+    }
+
+    /// Where the point `x`, `y` on the map is seen, the opposite of `point`.
+    fn point_back(self, x: f64, y: f64) -> (f64, f64) {
+        let (x, y) = (x - self.origin.0, y - self.origin.1);
+        let ((xx, xy), (yx, yy)) = (self.axis_x, self.axis_y);
+        (x * xx as f64 + y * xy as f64, x * yx as f64 + y * yy as f64)
+    }
+
+    /// Whether it shows things back to front, like seeing them in one mirror does.
+    fn flipped(self) -> bool {
+        let ((xx, xy), (yx, yy)) = (self.axis_x, self.axis_y);
+        xx * yy - xy * yx < 0
+    }
+}
+
+/// Where one leg of a column's ray starts, by distance, how it sees the map and what the
+/// mirrors so far have tinted it by.
+#[derive(Clone, Copy)]
+struct SeenLeg {
+    start: f64,
+    transform: Transform,
+    tint: [u16; 3],
+}
+
+/// The legs of a column's ray, up to the wall it ends at.
+#[derive(Clone, Copy)]
+struct ColumnLegs {
+    legs: [SeenLeg; MAX_LEGS],
+    count: usize,
+}
+
+impl ColumnLegs {
+    fn legs(&self) -> &[SeenLeg] {
+        &self.legs[..self.count]
+    }
+
+    /// The leg that shows what's `depth` away along the column, if it sees the map the way
+    /// `transform` does.
+    fn seeing(&self, transform: Transform, depth: f64) -> Option<&SeenLeg> {
+        let legs = self.legs();
+        legs.iter().enumerate().find_map(|(i, leg)| {
+            let end = legs.get(i + 1).map_or(f64::INFINITY, |next| next.start);
+            let seen = leg.transform == transform && depth > leg.start && depth < end;
+            seen.then_some(leg)
+        })
+    }
+}
+
+impl Default for ColumnLegs {
+    fn default() -> ColumnLegs {
+        ColumnLegs {
+            legs: [SeenLeg {
+                start: 0.0,
+                transform: Transform::IDENTITY,
+                tint: [256; 3],
+            }; MAX_LEGS],
+            count: 0,
+        }
+    }
+}
+
+/// One leg of a column's ray, from the distance it went through a portal or bounced off a
+/// mirror along its new direction, with how it sees the map and what the mirrors so far have
+/// tinted it by.
+#[derive(Clone, Copy)]
+struct Leg<T> {
+    start: T,
+    pos: (T, T),
+    dir: (T, T),
+    transform: Transform,
+    tint: [u16; 3],
+}
+
+/// A sprite where the camera sees it, which is somewhere else when it's seen through a
+/// portal or in a mirror.
+struct SeenSprite {
+    x: f64,
+    y: f64,
+    texture: usize,
+    scale_x: f64,
+    scale_y: f64,
+    v_move: f64,
+    transform: Transform,
+}
+
+/// Darkens `color` by `tint`, out of 256 for each of red, green and blue.
+fn apply_tint(color: &mut [u8], tint: [u16; 3]) {
+    for (component, tint) in color.iter_mut().zip(tint) {
+        *component = (*component as u16 * tint / 256) as u8;
+    }
+}
+
+/// Where a ray met a wall that doesn't fill its cell.
+#[derive(Clone, Copy)]
+struct ShapeHit<T> {
+    distance: T,
+    // how far along the wall's texture it is from 0 to 1, left to right as seen from outside
+    along: T,
+    // out of 256, from half for walls facing along y to full for walls facing along x, like
+    // the faces of a block
+    light: u16,
+}
+
+/// How brightly a wall facing along `normal` is lit, out of 256.
+fn light<T: Scalar>(normal: (T, T)) -> u16 {
+    let (x, y) = normal;
+    let facing_x = x * x / (x * x + y * y);
+    128 + (T::from_i32(128) * facing_x).to_i32() as u16
+}
+
+/// Where `ray` meets the diagonal wall in the cell it's in, if it does between distances
+/// `entry` and `exit`.
+fn hit_diagonal<T: Scalar>(corner: Corner, ray: &Ray<T>, entry: T, exit: T) -> Option<ShapeHit<T>> {
+    let (a, b, c) = corner.edge();
+    let (a, b, c) = (T::from_i32(a), T::from_i32(b), T::from_i32(c));
+    let zero = T::from_i32(0);
+    let x = ray.pos_x - T::from_i32(ray.map_x as i32);
+    let y = ray.pos_y - T::from_i32(ray.map_y as i32);
+    // the wall can only be seen from its open side
+    let facing = a * ray.dir_x + b * ray.dir_y;
+    if facing >= zero {
+        return None;
+    }
+    let distance = (c - a * x - b * y) / facing;
+    if distance < entry || distance > exit {
+        return None;
+    }
+
+    let hit_x = x + distance * ray.dir_x;
+    let along = if b > zero {
+        T::from_i32(1) - hit_x
+    } else {
+        hit_x
+    };
+    Some(ShapeHit {
+        distance,
+        along,
+        light: light((a, b)),
+    })
+}
+
+/// Where `ray` meets the outside of the pillar of `radius` in the middle of the cell it's in,
+/// if it does past distance `entry`.
+fn hit_cylinder<T: Scalar>(radius: f64, ray: &Ray<T>, entry: T) -> Option<ShapeHit<T>> {
+    let half = T::from_f64(0.5);
+    let radius = T::from_f64(radius);
+    let ray_dir = (ray.dir_x, ray.dir_y);
+    // from the ray's start to the middle of the pillar
+    let to_x = T::from_i32(ray.map_x as i32) + half - ray.pos_x;
+    let to_y = T::from_i32(ray.map_y as i32) + half - ray.pos_y;
+
+    // the closest the ray gets to the middle, along the ray and squared across it
+    let length_squared = ray_dir.0 * ray_dir.0 + ray_dir.1 * ray_dir.1;
+    let closest = (to_x * ray_dir.0 + to_y * ray_dir.1) / length_squared;
+    let across_x = to_x - closest * ray_dir.0;
+    let across_y = to_y - closest * ray_dir.1;
+    let across_squared = across_x * across_x + across_y * across_y;
+    if across_squared >= radius * radius {
+        return None;
+    }
+    let distance = closest - ((radius * radius - across_squared) / length_squared).sqrt();
+    if distance < entry {
+        return None;
+    }
+
+    // the hit's direction from the middle, which the texture wraps around once
+    let normal_x = distance * ray_dir.0 - to_x;
+    let normal_y = distance * ray_dir.1 - to_y;
+    let turn = T::from_f64(2.0 * PI);
+    let along = (normal_y.atan2(normal_x) + T::from_f64(PI)) / turn;
+    Some(ShapeHit {
+        distance,
+        along,
+        light: light((normal_x, normal_y)),
+    })
+}
+
+/// Copies the `strips` a frame was split into, each `strip_width` columns of it stored row by
+/// row, into `rows`, whole rows of the frame starting at `first_row`.
+fn join_strips(
+    strips: &[u8],
+    width: usize,
+    height: usize,
+    strip_width: usize,
+    first_row: usize,
+    rows: &mut [u8],
+) {
+    let row_count = rows.len() / (width * 3).max(1);
+    for (strip, pixels) in strips.chunks((strip_width * height * 3).max(1)).enumerate() {
+        let row_length = pixels.len() / height;
+        let start = strip * strip_width * 3;
+        for y in 0..row_count {
+            let from = (first_row + y) * row_length;
+            let to = y * width * 3 + start;
+            rows[to..to + row_length].copy_from_slice(&pixels[from..from + row_length]);
+        }
+    }
+}
+
+impl<T: FloorSpan> View<T> {
+    /// Draws the walls, floor and ceiling for the screen `columns` into `pixels`, which holds
+    /// just those columns for every row, and records each column's wall distance in
+    /// `z_buffer` and the legs of its ray in `column_legs`.
+    /// `texels` holds a column's floor texels, reused from column to column.
+    #[allow(clippy::too_many_arguments)]
+    fn cast_columns(
+        &self,
+        textures: &[Texture],
+        decals: &Decals,
+        columns: Range<usize>,
+        pixels: &mut [u8],
+        z_buffer: &mut [f64],
+        column_legs: &mut [ColumnLegs],
+        floor_rows: &FloorRows<T>,
+        texels: &mut [usize],
+    ) {
+        let View {
+            pos_x,
+            pos_y,
+            dir_x,
+            dir_y,
+            plane_x,
+            plane_y,
+            width,
+            height,
+            projection,
+            mipmapping,
+            filtering,
+            simd,
+            time,
+        } = *self;
+        let first_column = columns.start;
+        let zero = T::from_i32(0);
+        // the decals on the face each column hits, with the texture and the column of it
+        // they're drawn with
+        let mut column_decals = Vec::new();
+        let one = T::from_i32(1);
+
+        let row_length = columns.len() * 3;
+
+        for x in columns {
+            let column = &mut pixels[(x - first_column) * 3..];
+            let camera_x = T::from_i32(2) * T::from_i32(x as i32) / T::from_i32(width as i32) - one;
+            let ray_dir_x = dir_x + plane_x * camera_x;
+            let ray_dir_y = dir_y + plane_y * camera_x;
+
+            let mut ray = Ray::new(
+                (pos_x, pos_y),
+                (ray_dir_x, ray_dir_y),
+                (pos_x.to_i32() as usize, pos_y.to_i32() as usize),
+            );
+            let mut portals = 0;
+            let mut reflections = 0;
+            let mut legs = [Leg {
+                start: zero,
+                pos: (pos_x, pos_y),
+                dir: (ray_dir_x, ray_dir_y),
+                transform: Transform::IDENTITY,
+                tint: [256; 3],
+            }; MAX_LEGS];
+            let mut leg_count = 1;
+
+            // side 2 is a wall that isn't a block
+            let mut side = 0;
+            let mut shape_hit = None;
+            // the player can stand beside a wall that doesn't fill its cell
+            if WORLD_MAP[ray.map_x][ray.map_y] > 0 {
+                shape_hit = match shape(ray.map_x, ray.map_y) {
+                    Shape::Block => None,
+                    Shape::Diagonal(corner) => hit_diagonal(corner, &ray, zero, ray.exit()),
+                    Shape::Cylinder(radius) => hit_cylinder(radius, &ray, zero),
+                };
+            }
+            while shape_hit.is_none() {
+                let (face, crossing) = if ray.side_dist_x < ray.side_dist_y {
+                    let face = if ray.step_x < 0 {
+                        Face::MinX
+                    } else {
+                        Face::MaxX
+                    };
+                    (face, ray.side_dist_x)
+                } else {
+                    let face = if ray.step_y < 0 {
+                        Face::MinY
+                    } else {
+                        Face::MaxY
+                    };
+                    (face, ray.side_dist_y)
+                };
+                let through = portal(ray.map_x, ray.map_y, face).filter(|_| portals < MAX_PORTALS);
+                if let Some(index) = through {
+                    let portal = &PORTALS[index];
+                    let (pos, ray_dir) = (
+                        portal.point(ray.pos_x, ray.pos_y),
+                        portal.turn(ray.dir_x, ray.dir_y),
+                    );
+                    ray = Ray::new(pos, ray_dir, portal.target);
+                    side = match face {
+                        Face::MinX | Face::MaxX => 0,
+                        Face::MinY | Face::MaxY => 1,
+                    };
+                    if portal.turns % 2 == 1 {
+                        side = 1 - side;
+                    }
+                    portals += 1;
+                    // the floor past the portal is found from the far side, so it lines up
+                    // with the walls there however the portal turns
+                    let previous = legs[leg_count - 1];
+                    legs[leg_count] = Leg {
+                        start: crossing,
+                        pos,
+                        dir: ray_dir,
+                        transform: previous
+                            .transform
+                            .then(|x, y| portal.point(x, y), |x, y| portal.turn(x, y)),
+                        tint: previous.tint,
+                    };
+                    leg_count += 1;
+                } else if ray.side_dist_x < ray.side_dist_y {
+                    ray.side_dist_x = ray.side_dist_x + ray.delta_dist_x;
+                    ray.map_x = (ray.step_x + ray.map_x as i32) as usize;
+                    side = 0;
+                } else {
+                    ray.side_dist_y = ray.side_dist_y + ray.delta_dist_y;
+                    ray.map_y = (ray.step_y + ray.map_y as i32) as usize;
+                    side = 1;
+                }
+
+                let reflected =
+                    mirror(ray.map_x, ray.map_y).filter(|_| reflections < MAX_REFLECTIONS);
+                if let Some(index) = reflected {
+                    let face = ray.entry_face(side);
+                    // the cell in front of the mirror, which the ray goes back into
+                    let back = if side == 0 {
+                        ((ray.map_x as i32 - ray.step_x) as usize, ray.map_y)
+                    } else {
+                        (ray.map_x, (ray.map_y as i32 - ray.step_y) as usize)
+                    };
+                    let mirror = &MIRRORS[index];
+                    let crossing = ray.face_distance(side);
+                    let pos = face.reflect(mirror.cell, ray.pos_x, ray.pos_y);
+                    let ray_dir = face.reflect_dir(ray.dir_x, ray.dir_y);
+                    ray = Ray::new(pos, ray_dir, back);
+
+                    let previous = legs[leg_count - 1];
+                    let mut tint = previous.tint;
+                    for (tint, mirror_tint) in tint.iter_mut().zip(mirror.tint) {
+                        *tint = *tint * mirror_tint / 256;
+                    }
+                    legs[leg_count] = Leg {
+                        start: crossing,
+                        pos,
+                        dir: ray_dir,
+                        transform: previous.transform.then(
+                            |x, y| face.reflect(mirror.cell, x, y),
+                            |x, y| face.reflect_dir(x, y),
+                        ),
+                        tint,
+                    };
+                    leg_count += 1;
+                    reflections += 1;
+                }
+
+                // after a bounce this is the cell in front of the mirror, which can still hold
+                // a wall that doesn't fill it
+                let (map_x, map_y) = (ray.map_x, ray.map_y);
+                if WORLD_MAP[map_x][map_y] > 0 {
+                    let entry = ray.face_distance(side);
+                    shape_hit = match shape(map_x, map_y) {
+                        Shape::Block => break,
+                        Shape::Diagonal(corner) => {
+                            // the face the ray came in through can be on the solid side
+                            let (a, b, c) = corner.edge();
+                            let entry_x = ray.pos_x + entry * ray.dir_x - T::from_i32(map_x as i32);
+                            let entry_y = ray.pos_y + entry * ray.dir_y - T::from_i32(map_y as i32);
+                            if T::from_i32(a) * entry_x + T::from_i32(b) * entry_y < T::from_i32(c)
+                            {
+                                break;
+                            }
+                            hit_diagonal(corner, &ray, entry, ray.exit())
+                        }
+                        Shape::Cylinder(radius) => hit_cylinder(radius, &ray, entry),
+                    };
+                }
+            }
+            if shape_hit.is_some() {
+                side = 2;
+            }
+
+            let perp_wall_dist = match shape_hit {
+                Some(hit) => hit.distance,
+                None => ray.face_distance(side),
+            };
+            let line_height = (projection / perp_wall_dist).to_i32();
+            let mut draw_start = -line_height / 2 + height as i32 / 2;
+            if draw_start < 0 {
+                draw_start = 0;
+            }
+            let mut draw_end = line_height / 2 + height as i32 / 2;
+            if draw_end >= height as i32 {
+                draw_end = height as i32 - 1;
+            }
+
+            let face = match side {
+                2 => None,
+                _ => Some(ray.entry_face(side)),
+            };
+            // blocks can have a different texture on each face
+            let face_texture = face.and_then(|face| face_texture(ray.map_x, ray.map_y, face));
+            let tex_num = match face_texture {
+                Some(face_texture) => face_texture.texture,
+                None => WORLD_MAP[ray.map_x][ray.map_y] - 1,
+            };
+            let tex_num = current_frame(textures, tex_num, time);
+
+            let wall_x = match shape_hit {
+                Some(hit) => hit.along,
+                None => {
+                    let wall_x = if side == 0 {
+                        ray.pos_y + perp_wall_dist * ray.dir_y
+                    } else {
+                        ray.pos_x + perp_wall_dist * ray.dir_x
+                    };
+                    wall_x - wall_x.floor()
+                }
+            };
+
+            let texture_width = T::from_i32(TEXTURE_WIDTH as i32);
+            // a shaped wall can be hit right at its far end
+            let mut tex_x = ((wall_x * texture_width).to_i32() as usize).min(TEXTURE_WIDTH - 1);
+            if side == 0 && ray.dir_x > zero {
+                tex_x = TEXTURE_WIDTH - tex_x - 1;
+            }
+            if side == 1 && ray.dir_y < zero {
+                tex_x = TEXTURE_WIDTH - tex_x - 1;
+            }
+            // the same coordinate without rounding to a texel, for filtering
+            let mut tex_u = wall_x * texture_width;
+            if (side == 0 && ray.dir_x > zero) || (side == 1 && ray.dir_y < zero) {
+                tex_u = texture_width - tex_u;
+            }
+
+            column_decals.clear();
+            if let Some(face) = face {
+                let cell = (ray.map_x, ray.map_y);
+                let u = tex_u.to_f64() / TEXTURE_WIDTH as f64;
+                for decal in DECALS.iter().chain(decals.iter()) {
+                    if decal.cell != cell || decal.face != face {
+                        continue;
+                    }
+                    if let Some(x) = decal.texture_x(u) {
+                        let texture = current_frame(textures, decal.texture, time);
+                        column_decals.push((decal, texture, x));
+                    }
+                }
+            }
+
+            // a face's texture can be shifted across and down it, and scroll as time goes on
+            let mut tex_shift_y = zero;
+            if let Some(face_texture) = face_texture {
+                let shift = |offset: f64, scroll: f64, size: usize| {
+                    T::from_f64((offset + scroll * time).rem_euclid(1.0) * size as f64)
+                };
+                let (offset_x, offset_y) = face_texture.offset;
+                let (scroll_x, scroll_y) = face_texture.scroll;
+                tex_u = tex_u + shift(offset_x, scroll_x, TEXTURE_WIDTH);
+                if tex_u >= texture_width {
+                    tex_u = tex_u - texture_width;
+                }
+                tex_x = (tex_u.to_i32() as usize).min(TEXTURE_WIDTH - 1);
+                tex_shift_y = shift(offset_y, scroll_y, TEXTURE_HEIGHT);
+            }
+
+            let step = T::from_i32(TEXTURE_HEIGHT as i32) / T::from_i32(line_height);
+            let level = if mipmapping {
+                mip_level(step.to_f64())
+            } else {
+                0
+            };
+            let size = TEXTURE_HEIGHT >> level;
+            let texture = textures[tex_num].mip(level);
+            let tex_x = tex_x >> level;
+            let mip_scale = 1.0 / (1 << level) as f64;
+            let mut tex_pos =
+                T::from_i32(draw_start - height as i32 / 2 + line_height / 2) * step + tex_shift_y;
+            for y in draw_start..draw_end {
+                let mut color = match filtering {
+                    Filtering::Nearest => {
+                        let tex_y = (tex_pos.to_i32() as usize >> level) & (size - 1);
+                        let texel = (size * tex_x + tex_y) * 3;
+                        [texture[texel], texture[texel + 1], texture[texel + 2]]
+                    }
+                    Filtering::Bilinear => textures[tex_num].sample_bilinear(
+                        level,
+                        tex_u.to_f64() * mip_scale,
+                        (tex_pos + step / T::from_i32(2)).to_f64() * mip_scale,
+                    ),
+                };
+                // decals go over the wall before it's lit, newest on top
+                if !column_decals.is_empty() {
+                    let v = (tex_pos - tex_shift_y + step / T::from_i32(2)).to_f64()
+                        / TEXTURE_HEIGHT as f64;
+                    for &(decal, texture, x) in column_decals.iter() {
+                        let y = match decal.texture_y(v) {
+                            Some(y) => y,
+                            None => continue,
+                        };
+                        let (u, v) = (x * TEXTURE_WIDTH as f64, y * TEXTURE_HEIGHT as f64);
+                        let decal_color = match filtering {
+                            Filtering::Nearest => {
+                                let texel = (TEXTURE_HEIGHT * u as usize + v as usize) * 3;
+                                let texel = &textures[texture].pixels()[texel..texel + 3];
+                                Some([texel[0], texel[1], texel[2]])
+                                    .filter(|&texel| texel != [0, 0, 0])
+                            }
+                            Filtering::Bilinear => textures[texture].sample_bilinear_masked(u, v),
+                        };
+                        if let Some(decal_color) = decal_color {
+                            color = decal_color;
+                        }
+                    }
+                }
+                tex_pos = tex_pos + step;
+
+                for component in color.iter_mut() {
+                    match side {
+                        1 => *component /= 2,
+                        2 => {
+                            let light = shape_hit.map_or(256, |hit| hit.light);
+                            *component = (*component as u16 * light / 256) as u8
+                        }
+                        _ => (),
+                    }
+                }
+                apply_tint(&mut color, legs[leg_count - 1].tint);
+                let start = y as usize * row_length;
+                column[start..start + 3].copy_from_slice(&color);
+            }
+
+            // floor casting, only above and below the wall. The ceiling mirrors the floor
+            // across the horizon, whose own row is infinitely far away and never drawn.
+            let horizon = height / 2;
+            let floor_start = (draw_end as usize).max(horizon + 1);
+            let ceiling_end = (draw_start as usize).min(height - 1 - horizon);
+            // the ceiling row y sees the same spot as floor row height - 1 - y, so the floor
+            // under both is only found once
+            let first_row = floor_start.min(height - ceiling_end);
+            let floor_texture = &textures[current_frame(textures, FLOOR_TEXTURE, time)];
+            let ceiling_texture = &textures[current_frame(textures, CEILING_TEXTURE, time)];
+            let legs = &legs[..leg_count];
+            match filtering {
+                Filtering::Nearest => {
+                    // rows get nearer going down, so each leg of the ray sees the rows above
+                    // the ones the legs before it saw
+                    let distances = &floor_rows.distances[first_row..height];
+                    let mut rows_end = height;
+                    for (i, leg) in legs.iter().enumerate() {
+                        let rows_start = match legs.get(i + 1) {
+                            Some(next) => {
+                                first_row + distances.partition_point(|&d| d >= next.start)
+                            }
+                            None => first_row,
+                        };
+                        let rows = rows_start..rows_end;
+                        rows_end = rows_start;
+
+                        let texels = &mut texels[rows.clone()];
+                        T::floor_texels(
+                            leg.pos,
+                            leg.dir,
+                            &floor_rows.distances[rows.clone()],
+                            &floor_rows.mip_levels[rows.clone()],
+                            texels,
+                            simd,
+                        );
+                        let levels = &floor_rows.mip_levels[rows.clone()];
+                        for (y, (&texel, &level)) in rows.zip(texels.iter().zip(levels)) {
+                            if y >= floor_start {
+                                let mip = floor_texture.mip(level);
+                                let pixel = &mut column[y * row_length..y * row_length + 3];
+                                for i in 0..3 {
+                                    pixel[i] = mip[texel + i] / 2;
+                                }
+                                apply_tint(pixel, leg.tint);
+                            }
+                            if height - 1 - y < ceiling_end {
+                                let mip = ceiling_texture.mip(level);
+                                let start = (height - 1 - y) * row_length;
+                                let pixel = &mut column[start..start + 3];
+                                for i in 0..3 {
+                                    pixel[i] = mip[texel + i] / 2;
+                                }
+                                apply_tint(pixel, leg.tint);
+                            }
+                        }
+                    }
+                }
+                Filtering::Bilinear => {
+                    let floor_color = |texture: &Texture, y: usize| {
+                        let distance = floor_rows.distances[y];
+                        let mip_level = floor_rows.mip_levels[y];
+                        // the first leg starts right at the camera, so one always matches
+                        let leg = legs.iter().rev().find(|leg| distance >= leg.start).unwrap();
+                        let floor_x = leg.pos.0 + distance * leg.dir.0;
+                        let floor_y = leg.pos.1 + distance * leg.dir.1;
+                        let cell_x = floor_x.to_i32();
+                        let cell_y = floor_y.to_i32();
+
+                        let size = T::from_i32((TEXTURE_HEIGHT >> mip_level) as i32);
+                        let u = size * (floor_x - T::from_i32(cell_x));
+                        let v = size * (floor_y - T::from_i32(cell_y));
+                        let mut color = texture.sample_bilinear(mip_level, u.to_f64(), v.to_f64());
+                        for component in color.iter_mut() {
+                            *component /= 2;
+                        }
+                        apply_tint(&mut color, leg.tint);
+                        color
+                    };
+
+                    for y in floor_start..height {
+                        let color = floor_color(floor_texture, y);
+                        column[y * row_length..y * row_length + 3].copy_from_slice(&color);
+                    }
+                    for y in 0..ceiling_end {
+                        let color = floor_color(ceiling_texture, height - 1 - y);
+                        column[y * row_length..y * row_length + 3].copy_from_slice(&color);
+                    }
+                }
+            }
+            // rows around the horizon a far enough wall leaves uncovered
+            for y in (ceiling_end..draw_start as usize).chain(draw_end as usize..floor_start) {
+                for i in 0..3 {
+                    column[y * row_length + i] = 0;
+                }
+            }
+
+            // reflective floors blend in the column above them mirrored across the bottom of
+            // the wall, lit like the floor they're seen in
+            let wall_bottom = line_height / 2 + height as i32 / 2;
+            for y in floor_start..height {
+                let distance = floor_rows.distances[y];
+                let leg = legs.iter().rev().find(|leg| distance >= leg.start).unwrap();
+                let floor_x = leg.pos.0 + distance * leg.dir.0;
+                let floor_y = leg.pos.1 + distance * leg.dir.1;
+                let (cell_x, cell_y) = (floor_x.to_i32() as usize, floor_y.to_i32() as usize);
+                let material = match floor_material(cell_x, cell_y) {
+                    Some(material) => material,
+                    None => continue,
+                };
+
+                // in the casting number type, so fixed-point frames don't depend on how the
+                // platform works out a sine. Only the fraction of a wave the time adds matters,
+                // which keeps the phase small enough for fixed point however long it's been.
+                let waves = T::from_f64(RIPPLE_DENSITY) * (floor_x + floor_y)
+                    + T::from_f64((RIPPLE_SPEED * time).fract());
+                let phase = T::from_f64(2.0 * PI) * (waves - waves.floor());
+                let ripple =
+                    T::from_f64(material.ripple) * T::from_i32((y - horizon) as i32) * phase.sin();
+                let mirrored = 2 * wall_bottom - y as i32 + ripple.to_i32();
+                let mirrored = mirrored.clamp(0, floor_start as i32 - 1) as usize;
+
+                let reflectivity = material.reflectivity;
+                for i in 0..3 {
+                    let floor = column[y * row_length + i] as u16;
+                    let reflection = column[mirrored * row_length + i] as u16 / 2;
+                    column[y * row_length + i] =
+                        ((floor * (256 - reflectivity) + reflection * reflectivity) / 256) as u8;
+                }
+            }
+
+            z_buffer[x - first_column] = perp_wall_dist.to_f64();
+            let column_legs = &mut column_legs[x - first_column];
+            column_legs.count = legs.len();
+            for (seen, leg) in column_legs.legs.iter_mut().zip(legs) {
+                *seen = SeenLeg {
+                    start: leg.start.to_f64(),
+                    transform: leg.transform,
+                    tint: leg.tint,
+                };
+            }
+        }
+    }
+
+    /// Draws the sprites, furthest first, over the screen `columns` held in `pixels`, hiding
+    /// them behind walls closer than their entry in `z_buffer`. Sprites seen through portals
+    /// or in mirrors only show where a leg of the column's ray in `column_legs` saw them there.
+    #[allow(clippy::too_many_arguments)]
+    fn cast_sprites(
+        &self,
+        textures: &[Texture],
+        sprites: &[SeenSprite],
+        sprite_order: &[usize],
+        columns: Range<usize>,
+        pixels: &mut [u8],
+        z_buffer: &[f64],
+        column_legs: &[ColumnLegs],
+    ) {
+        let View {
+            pos_x,
+            pos_y,
+            dir_x,
+            dir_y,
+            plane_x,
+            plane_y,
+            width,
+            height,
+            projection,
+            filtering,
+            time,
+            ..
+        } = *self;
+        let first_column = columns.start;
+        let row_length = columns.len() * 3;
+        let one = T::from_i32(1);
+
+        for &index in sprite_order {
+            let sprite = &sprites[index];
+            // a sprite in a mirror is seen back to front
+            let flipped = sprite.transform.flipped();
+            let sprite_x = T::from_f64(sprite.x) - pos_x;
+            let sprite_y = T::from_f64(sprite.y) - pos_y;
+
+            let inv_det = one / (plane_x * dir_y - dir_x * plane_y);
+
+            let transform_x = inv_det * (dir_y * sprite_x - dir_x * sprite_y);
+            let transform_y = inv_det * (-plane_y * sprite_x + plane_x * sprite_y);
+            // behind the camera
+            if transform_y <= T::from_i32(0) {
+                continue;
+            }
+
+            let sprite_screen_x = ((T::from_i32(width as i32) / T::from_i32(2))
+                * (one + transform_x / transform_y))
+                .to_i32();
+
+            // how far the sprite is moved down the screen, shrinking with distance like it does
+            let v_move_screen = (T::from_f64(sprite.v_move) * projection / transform_y).to_i32();
+
+            let sprite_height = (T::from_f64(sprite.scale_y) * projection / transform_y)
+                .abs()
+                .to_i32();
+            let middle_y = (height as i32 / 2).saturating_add(v_move_screen);
+            let mut draw_start_y = middle_y.saturating_sub(sprite_height / 2);
+            if draw_start_y < 0 {
+                draw_start_y = 0;
+            }
+            let mut draw_end_y = middle_y.saturating_add(sprite_height / 2);
+            if draw_end_y >= height as i32 {
+                draw_end_y = height as i32 - 1;
+            }
+
+            let sprite_width = (T::from_f64(sprite.scale_x) * projection / transform_y)
+                .abs()
+                .to_i32();
+            // a sprite right in front of the camera can be far bigger than the screen
+            let mut draw_start_x = sprite_screen_x.saturating_sub(sprite_width / 2);
+            if draw_start_x < 0 {
+                draw_start_x = 0;
+            }
+            let mut draw_end_x = sprite_screen_x.saturating_add(sprite_width / 2);
+            if draw_end_x >= width as i32 {
+                draw_end_x = width as i32 - 1;
+            }
+
+            // only the part of the sprite inside these columns
+            let draw_start_x = draw_start_x.max(columns.start as i32);
+            let draw_end_x = draw_end_x.min(columns.end as i32);
+
+            for stripe in draw_start_x..draw_end_x {
+                let mut tex_x = (256
+                    * (stripe as i64 - (-sprite_width as i64 / 2 + sprite_screen_x as i64))
+                    * TEXTURE_WIDTH as i64
+                    / sprite_width as i64)
+                    / 256;
+                if flipped {
+                    tex_x = TEXTURE_WIDTH as i64 - 1 - tex_x;
+                }
+                let depth = transform_y.to_f64();
+                // each leg of the ray only shows sprites seen the way it sees the map, up to
+                // the next portal or mirror, tinted by the mirrors before it
+                let seen =
+                    column_legs[stripe as usize - first_column].seeing(sprite.transform, depth);
+                let tint = match seen {
+                    Some(leg) => leg.tint,
+                    None => continue,
+                };
+                if stripe > 0
+                    && stripe < width as i32
+                    && depth < z_buffer[stripe as usize - first_column]
+                {
+                    let column = &mut pixels[(stripe as usize - first_column) * 3..];
+                    let texture = &textures[current_frame(textures, sprite.texture, time)];
+                    let texture_column = &texture.pixels()[TEXTURE_HEIGHT * tex_x as usize * 3..]
+                        [..TEXTURE_HEIGHT * 3];
+                    // texture coordinates through the middle of the pixel, for filtering
+                    let u = (stripe as i64 - (sprite_screen_x as i64 - sprite_width as i64 / 2))
+                        as f64
+                        + 0.5;
+                    let mut u = u * TEXTURE_WIDTH as f64 / sprite_width as f64;
+                    if flipped {
+                        u = TEXTURE_WIDTH as f64 - u;
+                    }
+                    for y in draw_start_y..draw_end_y {
+                        let start = y as usize * row_length;
+                        let pixel = &mut column[start..start + 3];
+                        if filtering == Filtering::Bilinear {
+                            let v = (y as i64 - middle_y as i64) as f64
+                                + 0.5
+                                + sprite_height as f64 / 2.0;
+                            let v = v * TEXTURE_HEIGHT as f64 / sprite_height as f64;
+                            if let Some(color) = texture.sample_bilinear_masked(u, v) {
+                                pixel.clone_from_slice(&color);
+                                apply_tint(pixel, tint);
+                            }
+                            continue;
+                        }
+
+                        // how far down from the top of the sprite the pixel is, in 256ths,
+                        // measured from the same middle row the drawn range is
+                        let d = (y as i64 - middle_y as i64) * 256 + sprite_height as i64 * 128;
+                        let tex_y = (((d * TEXTURE_HEIGHT as i64) / sprite_height as i64) / 256)
+                            .clamp(0, TEXTURE_HEIGHT as i64 - 1);
+
+                        let color = &texture_column[tex_y as usize * 3..tex_y as usize * 3 + 3];
+                        // don't draw the black pixels
+                        if !(color[0] == 0 && color[1] == 0 && color[2] == 0) {
+                            pixel.clone_from_slice(color);
+                            apply_tint(pixel, tint);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Renderer {
+        Renderer::new(SCREEN_WIDTH, SCREEN_HEIGHT)
+    }
+}
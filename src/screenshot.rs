@@ -0,0 +1,22 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// Writes an RGB24 frame out to `path` as a PNG.
+pub fn save_png(
+    path: impl AsRef<Path>,
+    buffer: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("{:?}", e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::RGB);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| format!("{:?}", e))?;
+    writer
+        .write_image_data(buffer)
+        .map_err(|e| format!("{:?}", e))?;
+
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use crate::fixed::{Fixed, Scalar};
+use crate::texture::TEXTURE_HEIGHT;
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// Finds the floor texels seen along a run of rows in one screen column. The floor under row
+/// `i` is `distances[i]` along the ray from `pos`, and `texels[i]` gets where its texel starts
+/// in mip level `mip_levels[i]`, for nearest-neighbor sampling.
+pub(crate) trait FloorSpan: Scalar {
+    fn floor_texels(
+        pos: (Self, Self),
+        ray_dir: (Self, Self),
+        distances: &[Self],
+        mip_levels: &[usize],
+        texels: &mut [usize],
+        _simd: bool,
+    ) {
+        floor_texels_scalar(pos, ray_dir, distances, mip_levels, texels);
+    }
+}
+
+impl FloorSpan for Fixed {}
+
+impl FloorSpan for f64 {
+    fn floor_texels(
+        pos: (f64, f64),
+        ray_dir: (f64, f64),
+        distances: &[f64],
+        mip_levels: &[usize],
+        texels: &mut [usize],
+        simd: bool,
+    ) {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if simd && is_x86_feature_detected!("avx") {
+                // four rows at a time, then whatever is left over one by one
+                let vectorized = distances.len() / 4 * 4;
+                unsafe {
+                    floor_texels_avx(pos, ray_dir, distances, mip_levels, texels, vectorized);
+                }
+                floor_texels_scalar(
+                    pos,
+                    ray_dir,
+                    &distances[vectorized..],
+                    &mip_levels[vectorized..],
+                    &mut texels[vectorized..],
+                );
+                return;
+            }
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = simd;
+        floor_texels_scalar(pos, ray_dir, distances, mip_levels, texels);
+    }
+}
+
+/// Whether this CPU has a vectorized floor texel lookup.
+pub fn simd_available() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+fn floor_texels_scalar<T: Scalar>(
+    (pos_x, pos_y): (T, T),
+    (ray_dir_x, ray_dir_y): (T, T),
+    distances: &[T],
+    mip_levels: &[usize],
+    texels: &mut [usize],
+) {
+    for ((texel, &distance), &mip_level) in texels.iter_mut().zip(distances).zip(mip_levels) {
+        let floor_x = pos_x + distance * ray_dir_x;
+        let floor_y = pos_y + distance * ray_dir_y;
+        let cell_x = floor_x.to_i32();
+        let cell_y = floor_y.to_i32();
+
+        let size = TEXTURE_HEIGHT >> mip_level;
+        let u = T::from_i32(size as i32) * (floor_x - T::from_i32(cell_x));
+        let v = T::from_i32(size as i32) * (floor_y - T::from_i32(cell_y));
+        let tx = u.to_i32() as usize & (size - 1);
+        let ty = v.to_i32() as usize & (size - 1);
+        *texel = (size * tx + ty) * 3;
+    }
+}
+
+/// The same as `floor_texels_scalar` for the first `count` rows, a multiple of four, with the
+/// same rounding at every step so both give identical texels.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx")]
+unsafe fn floor_texels_avx(
+    (pos_x, pos_y): (f64, f64),
+    (ray_dir_x, ray_dir_y): (f64, f64),
+    distances: &[f64],
+    mip_levels: &[usize],
+    texels: &mut [usize],
+    count: usize,
+) {
+    const TRUNCATE: i32 = _MM_FROUND_TO_ZERO | _MM_FROUND_NO_EXC;
+
+    let pos_x = _mm256_set1_pd(pos_x);
+    let pos_y = _mm256_set1_pd(pos_y);
+    let ray_dir_x = _mm256_set1_pd(ray_dir_x);
+    let ray_dir_y = _mm256_set1_pd(ray_dir_y);
+    let three = _mm_set1_epi32(3);
+    let one = _mm_set1_epi32(1);
+
+    for row in (0..count).step_by(4) {
+        let distance = _mm256_loadu_pd(distances[row..row + 4].as_ptr());
+        let floor_x = _mm256_add_pd(pos_x, _mm256_mul_pd(distance, ray_dir_x));
+        let floor_y = _mm256_add_pd(pos_y, _mm256_mul_pd(distance, ray_dir_y));
+        let cell_x = _mm256_round_pd::<TRUNCATE>(floor_x);
+        let cell_y = _mm256_round_pd::<TRUNCATE>(floor_y);
+
+        let levels = &mip_levels[row..row + 4];
+        let size = _mm_set_epi32(
+            (TEXTURE_HEIGHT >> levels[3]) as i32,
+            (TEXTURE_HEIGHT >> levels[2]) as i32,
+            (TEXTURE_HEIGHT >> levels[1]) as i32,
+            (TEXTURE_HEIGHT >> levels[0]) as i32,
+        );
+        let size_f = _mm256_cvtepi32_pd(size);
+        let u = _mm256_mul_pd(size_f, _mm256_sub_pd(floor_x, cell_x));
+        let v = _mm256_mul_pd(size_f, _mm256_sub_pd(floor_y, cell_y));
+
+        let mask = _mm_sub_epi32(size, one);
+        let tx = _mm_and_si128(_mm256_cvttpd_epi32(u), mask);
+        let ty = _mm_and_si128(_mm256_cvttpd_epi32(v), mask);
+        let texel = _mm_mullo_epi32(_mm_add_epi32(_mm_mullo_epi32(size, tx), ty), three);
+
+        let mut lanes = [0i32; 4];
+        _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, texel);
+        for (texel, lane) in texels[row..row + 4].iter_mut().zip(lanes) {
+            *texel = lane as usize;
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use crate::fixed::Scalar;
+use crate::texture::texture;
+use std::cmp::Ordering;
+use std::f64::consts::PI;
+
+pub struct Sprite {
+    pub x: f64,
+    pub y: f64,
+    pub texture: usize,
+    /// How wide and tall the sprite is drawn, as a fraction of a wall's height.
+    pub scale_x: f64,
+    pub scale_y: f64,
+    /// How far the middle of the sprite is below the middle of the wall, in wall heights, so
+    /// 0.25 puts a half-height sprite on the floor and -0.25 hangs it from the ceiling.
+    pub v_move: f64,
+    /// Which way the sprite faces and how it looks from each side, for sprites that don't
+    /// look the same all the way around. `texture` isn't used when they're set.
+    pub rotations: Option<Rotations>,
+}
+
+/// Eight images of a sprite seen from all around, like the guards in Wolfenstein 3D.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rotations {
+    /// The angle the sprite faces in radians, measured like the camera's from the x axis
+    /// toward the y axis.
+    pub facing: f64,
+    /// Indices into the textures, the first seen from in front and each of the others from
+    /// an eighth of a turn further around, toward larger angles.
+    pub frames: [usize; 8],
+}
+
+impl Sprite {
+    /// The texture showing the sprite as it's seen from `x`, `y`. It's worked out in the
+    /// casting number type, so fixed-point frames pick the same one on every machine.
+    pub fn texture_from<T: Scalar>(&self, x: T, y: T) -> usize {
+        let rotations = match self.rotations {
+            Some(rotations) => rotations,
+            None => return self.texture,
+        };
+        let angle = (y - T::from_f64(self.y)).atan2(x - T::from_f64(self.x))
+            - T::from_f64(rotations.facing);
+        // to the nearest eighth of a turn
+        let eighth = (angle * T::from_f64(4.0 / PI) + T::from_f64(0.5))
+            .floor()
+            .to_i32();
+        rotations.frames[eighth.rem_euclid(8) as usize]
+    }
+}
+
+pub const NUM_SPRITES: usize = 22;
+
+pub const SPRITES: [Sprite; NUM_SPRITES] = [
+    // green light in front of playerstart
+    Sprite {
+        x: 20.5,
+        y: 11.5,
+        texture: texture("greenlight"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    // green lights in every room
+    Sprite {
+        x: 18.5,
+        y: 4.5,
+        texture: texture("greenlight"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 10.0,
+        y: 4.5,
+        texture: texture("greenlight"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    // this one flickers
+    Sprite {
+        x: 10.0,
+        y: 12.5,
+        texture: texture("flickering_light"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 3.5,
+        y: 6.5,
+        texture: texture("greenlight"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 3.5,
+        y: 20.5,
+        texture: texture("greenlight"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 3.5,
+        y: 14.5,
+        texture: texture("greenlight"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 14.5,
+        y: 20.5,
+        texture: texture("greenlight"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    // row of pillars in front of wall: fisheye test
+    Sprite {
+        x: 18.5,
+        y: 10.5,
+        texture: texture("pillar"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 18.5,
+        y: 11.5,
+        texture: texture("pillar"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 18.5,
+        y: 12.5,
+        texture: texture("pillar"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    // some barrels around the map
+    Sprite {
+        x: 21.5,
+        y: 1.5,
+        texture: texture("pillar"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 15.5,
+        y: 1.5,
+        texture: texture("barrel"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 16.0,
+        y: 1.5,
+        texture: texture("barrel"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 16.2,
+        y: 1.8,
+        texture: texture("barrel"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 3.5,
+        y: 2.5,
+        texture: texture("barrel"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 9.5,
+        y: 15.5,
+        texture: texture("barrel"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 10.0,
+        y: 15.1,
+        texture: texture("barrel"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    Sprite {
+        x: 10.5,
+        y: 15.8,
+        texture: texture("barrel"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: None,
+    },
+    // a small barrel on the floor and a lamp hanging over it
+    Sprite {
+        x: 12.5,
+        y: 20.5,
+        texture: texture("barrel"),
+        scale_x: 0.5,
+        scale_y: 0.5,
+        v_move: 0.25,
+        rotations: None,
+    },
+    Sprite {
+        x: 12.0,
+        y: 19.5,
+        texture: texture("greenlight"),
+        scale_x: 0.5,
+        scale_y: 0.5,
+        v_move: -0.25,
+        rotations: None,
+    },
+    // a guard keeping watch down the long hall
+    Sprite {
+        x: 9.5,
+        y: 5.5,
+        texture: texture("guard0"),
+        scale_x: 1.0,
+        scale_y: 1.0,
+        v_move: 0.0,
+        rotations: Some(Rotations {
+            facing: PI / 2.0,
+            frames: [
+                texture("guard0"),
+                texture("guard1"),
+                texture("guard2"),
+                texture("guard3"),
+                texture("guard4"),
+                texture("guard5"),
+                texture("guard6"),
+                texture("guard7"),
+            ],
+        }),
+    },
+];
+
+pub fn sort_sprites(sprite_order: &mut [usize], sprite_distance: &mut [f64], num_sprites: usize) {
+    let mut sprites = Vec::with_capacity(num_sprites);
+    for i in 0..num_sprites {
+        sprites.push((sprite_distance[i], sprite_order[i]));
+    }
+
+    sprites.sort_by(|(a1, _), (b1, _)| b1.partial_cmp(a1).unwrap_or(Ordering::Equal));
+
+    for (i, (first, second)) in sprites.iter().enumerate() {
+        sprite_distance[i] = *first;
+        sprite_order[i] = *second;
+    }
+}
@@ -0,0 +1,37 @@
+/// How the two eyes of a stereo frame are put together.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stereo {
+    /// A single view from the camera.
+    Off,
+    /// The left eye in the red channel and the right eye in green and blue, for red/cyan
+    /// glasses.
+    Anaglyph,
+    /// The left eye squeezed into the left half of the frame and the right eye into the right
+    /// half, for viewers.
+    SideBySide,
+}
+
+/// Replaces the green and blue channels of the RGB24 `left` frame with those of `right`.
+pub fn anaglyph(left: &mut [u8], right: &[u8]) {
+    for (left, right) in left.chunks_exact_mut(3).zip(right.chunks_exact(3)) {
+        left[1] = right[1];
+        left[2] = right[2];
+    }
+}
+
+/// Copies the RGB24 `eye` frame, `eye_width` pixels wide, into the columns of `frame` that
+/// start at `first_column`.
+pub fn place_eye(
+    frame: &mut [u8],
+    width: usize,
+    eye: &[u8],
+    eye_width: usize,
+    first_column: usize,
+) {
+    for (row, pixels) in frame
+        .chunks_exact_mut(width * 3)
+        .zip(eye.chunks_exact(eye_width * 3))
+    {
+        row[first_column * 3..(first_column + eye_width) * 3].copy_from_slice(pixels);
+    }
+}
@@ -0,0 +1,73 @@
+/// The filter a supersampled frame is shrunk back down with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Downsample {
+    /// Averages the block of samples behind each pixel.
+    Box,
+    /// Weighs samples by their distance from the pixel center, reaching into the neighboring
+    /// pixels for a softer edge.
+    Tent,
+}
+
+/// Shrinks the RGB24 `source`, `factor` times the size of `rows` in both directions, into
+/// `rows`, whole rows of a `width` pixel wide frame starting at `first_row`.
+pub fn downsample(
+    source: &[u8],
+    width: usize,
+    height: usize,
+    factor: usize,
+    filter: Downsample,
+    first_row: usize,
+    rows: &mut [u8],
+) {
+    let source_width = width * factor;
+    let columns: Vec<_> = (0..width).map(|x| taps(x, factor, width, filter)).collect();
+
+    for (row, pixels) in rows.chunks_exact_mut(width * 3).enumerate() {
+        let row_taps = taps(first_row + row, factor, height, filter);
+        for (column_taps, pixel) in columns.iter().zip(pixels.chunks_exact_mut(3)) {
+            let mut color = [0.0; 3];
+            for &(source_y, weight_y) in &row_taps {
+                let source_row = &source[source_y * source_width * 3..];
+                for &(source_x, weight_x) in column_taps {
+                    let weight = weight_y * weight_x;
+                    let sample = &source_row[source_x * 3..source_x * 3 + 3];
+                    for (channel, &value) in color.iter_mut().zip(sample) {
+                        *channel += weight * value as f32;
+                    }
+                }
+            }
+            for (channel, value) in pixel.iter_mut().zip(color) {
+                *channel = value.round() as u8;
+            }
+        }
+    }
+}
+
+/// The samples along one axis that make up pixel `index` of `size`, and how much each one
+/// counts. The weights add up to one.
+fn taps(index: usize, factor: usize, size: usize, filter: Downsample) -> Vec<(usize, f32)> {
+    let first = index * factor;
+    match filter {
+        Downsample::Box => (first..first + factor)
+            .map(|sample| (sample, 1.0 / factor as f32))
+            .collect(),
+        Downsample::Tent => {
+            // the tent is two pixels wide, so it reaches half a pixel into each neighbor
+            let center = first as f32 + factor as f32 / 2.0;
+            let start = first.saturating_sub(factor / 2);
+            let end = (first + factor + factor / 2).min(size * factor);
+            let mut taps: Vec<_> = (start..end)
+                .map(|sample| {
+                    let distance = (sample as f32 + 0.5 - center).abs();
+                    (sample, (1.0 - distance / factor as f32).max(0.0))
+                })
+                .collect();
+            // the edges of the frame lose the samples past them
+            let total: f32 = taps.iter().map(|&(_, weight)| weight).sum();
+            for (_, weight) in &mut taps {
+                *weight /= total;
+            }
+            taps
+        }
+    }
+}
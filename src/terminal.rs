@@ -0,0 +1,271 @@
+use crate::backend::{Action, Backend, InputEvent};
+use std::env;
+use std::io::{self, Read, Stdout, Write};
+use std::mem;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how long a key press acts for, longer than terminals wait before repeating a held key so
+// holding one down doesn't stop and start
+const HOLD: Duration = Duration::from_millis(500);
+// frames are drawn at most this often, so the terminal keeps up
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+// the upper half block, colored with the top pixel in front and the bottom one behind
+const HALF_BLOCK: &str = "\u{2580}";
+
+/// Draws frames in the terminal with 24-bit ANSI colors, two pixels to a character cell using
+/// half blocks. Frames are rendered at the terminal's size, following it when it's resized.
+/// When standard input is a terminal it's put in raw mode so keys arrive as they're pressed.
+/// A terminal can't tell when a key is let go, so each key press acts for a short while.
+pub struct Terminal {
+    stdout: Stdout,
+    // what each read of standard input got
+    keys: Receiver<Vec<u8>>,
+    // the terminal settings to put back, when raw mode was turned on
+    original_mode: Option<libc::termios>,
+    held: Vec<(Action, Instant)>,
+    text: Vec<u8>,
+    last_frame: Instant,
+    last_present: Instant,
+}
+
+impl Terminal {
+    pub fn new() -> Result<Terminal, String> {
+        let original_mode = enable_raw_mode();
+
+        let (sender, keys) = mpsc::channel();
+        thread::spawn(move || {
+            // each read is sent whole, so the bytes of an arrow key's escape sequence, which
+            // the terminal writes all at once, arrive together
+            let mut stdin = io::stdin().lock();
+            let mut buffer = [0; 64];
+            loop {
+                match stdin.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(read) if sender.send(buffer[..read].to_vec()).is_ok() => (),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                    _ => break,
+                }
+            }
+        });
+
+        let mut stdout = io::stdout();
+        // switch to the alternate screen and hide the cursor
+        stdout
+            .write_all(b"\x1b[?1049h\x1b[?25l")
+            .and_then(|_| stdout.flush())
+            .map_err(|e| format!("{:?}", e))?;
+
+        Ok(Terminal {
+            stdout,
+            keys,
+            original_mode,
+            held: Vec::new(),
+            text: Vec::new(),
+            last_frame: Instant::now(),
+            last_present: Instant::now(),
+        })
+    }
+
+    fn press(&mut self, action: Action, events: &mut Vec<InputEvent>) {
+        // pressing a held key again keeps it held for longer
+        self.held.retain(|&(held, _)| held != action);
+        self.held.push((action, Instant::now()));
+        events.push(InputEvent::Pressed(action));
+    }
+
+    /// Presses the keys in `read`, one read of standard input.
+    fn read_keys(&mut self, read: Vec<u8>, events: &mut Vec<InputEvent>) {
+        let mut keys = read.into_iter();
+        while let Some(key) = keys.next() {
+            let action = match key {
+                // the arrow keys come as escape sequences, and escape read on its own quits
+                0x1b => match (keys.next(), keys.next()) {
+                    (Some(b'['), Some(b'A')) => Action::MoveForward,
+                    (Some(b'['), Some(b'B')) => Action::MoveBackward,
+                    (Some(b'['), Some(b'C')) => Action::TurnRight,
+                    (Some(b'['), Some(b'D')) => Action::TurnLeft,
+                    (None, _) => Action::Quit,
+                    _ => continue,
+                },
+                b'w' => Action::MoveForward,
+                b's' => Action::MoveBackward,
+                b'a' => Action::TurnLeft,
+                b'd' => Action::TurnRight,
+                b'-' => Action::NarrowFov,
+                b'=' => Action::WidenFov,
+                b'm' => Action::ToggleMipmaps,
+                b'b' => Action::ToggleFiltering,
+                b'n' => Action::CycleSupersampling,
+                b'x' => Action::ToggleFixedPoint,
+                b'p' => Action::TogglePostProcessing,
+                b'v' => Action::CycleStereo,
+                b' ' => Action::Shoot,
+                // raw mode turns ctrl-c into a plain key
+                b'q' | 0x03 => Action::Quit,
+                _ => continue,
+            };
+            self.press(action, events);
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self
+            .stdout
+            .write_all(b"\x1b[0m\x1b[?25h\x1b[?1049l")
+            .and_then(|_| self.stdout.flush());
+        if let Some(mode) = self.original_mode {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &mode);
+            }
+        }
+    }
+}
+
+impl Backend for Terminal {
+    fn present(&mut self, frame: &[u8], width: usize, height: usize) -> Result<(), String> {
+        let (columns, rows) = terminal_size();
+
+        self.text.clear();
+        // back to the top left corner, over the last frame
+        self.text.extend_from_slice(b"\x1b[H");
+        // colors are only sent when they change from the cell before
+        let (mut last_top, mut last_bottom) = (None, None);
+        for row in 0..rows {
+            if row > 0 {
+                self.text.extend_from_slice(b"\r\n");
+            }
+            for column in 0..columns {
+                let top = average(frame, width, height, column, 2 * row, columns, rows * 2);
+                let bottom = average(frame, width, height, column, 2 * row + 1, columns, rows * 2);
+                if last_top != Some(top) {
+                    let [red, green, blue] = top;
+                    write!(self.text, "\x1b[38;2;{};{};{}m", red, green, blue)
+                        .map_err(|e| format!("{:?}", e))?;
+                    last_top = Some(top);
+                }
+                if last_bottom != Some(bottom) {
+                    let [red, green, blue] = bottom;
+                    write!(self.text, "\x1b[48;2;{};{};{}m", red, green, blue)
+                        .map_err(|e| format!("{:?}", e))?;
+                    last_bottom = Some(bottom);
+                }
+                self.text.extend_from_slice(HALF_BLOCK.as_bytes());
+            }
+        }
+        self.text.extend_from_slice(b"\x1b[0m");
+
+        if let Some(wait) = FRAME_INTERVAL.checked_sub(self.last_present.elapsed()) {
+            thread::sleep(wait);
+        }
+        self.last_present = Instant::now();
+        self.stdout
+            .write_all(&self.text)
+            .and_then(|_| self.stdout.flush())
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    fn poll_events(&mut self, events: &mut Vec<InputEvent>) -> Result<(), String> {
+        self.held.retain(|&(action, pressed)| {
+            let expired = pressed.elapsed() >= HOLD;
+            if expired {
+                events.push(InputEvent::Released(action));
+            }
+            !expired
+        });
+
+        while let Ok(read) = self.keys.try_recv() {
+            self.read_keys(read, events);
+        }
+
+        Ok(())
+    }
+
+    fn frame_time(&mut self) -> f64 {
+        let now = Instant::now();
+        let frame_time = (now - self.last_frame).as_secs_f64();
+        self.last_frame = now;
+        frame_time
+    }
+
+    fn resolution(&mut self) -> Option<(usize, usize)> {
+        let (columns, rows) = terminal_size();
+        Some((columns, rows * 2))
+    }
+}
+
+/// Turns off line buffering and echo on standard input, returning the settings to restore.
+/// Does nothing when it isn't a terminal.
+fn enable_raw_mode() -> Option<libc::termios> {
+    unsafe {
+        let mut mode: libc::termios = mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut mode) != 0 {
+            return None;
+        }
+        let original_mode = mode;
+        libc::cfmakeraw(&mut mode);
+        if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &mode) != 0 {
+            return None;
+        }
+        Some(original_mode)
+    }
+}
+
+/// The terminal's size in character cells. When standard output isn't a terminal it comes
+/// from `COLUMNS` and `LINES`, or is 80 by 24 without them.
+fn terminal_size() -> (usize, usize) {
+    unsafe {
+        let mut size: libc::winsize = mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0
+            && size.ws_col > 0
+            && size.ws_row > 0
+        {
+            return (size.ws_col as usize, size.ws_row as usize);
+        }
+    }
+
+    let size = |name, default| {
+        env::var(name)
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|&size| size > 0)
+            .unwrap_or(default)
+    };
+    (size("COLUMNS", 80), size("LINES", 24))
+}
+
+/// The average color of the pixels in cell `x`, `y` of a `columns` by `rows` grid laid over a
+/// `width` by `height` frame.
+fn average(
+    frame: &[u8],
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+    columns: usize,
+    rows: usize,
+) -> [u8; 3] {
+    // every cell covers at least one pixel, even when the frame is smaller than the grid
+    let left = x * width / columns;
+    let right = ((x + 1) * width / columns).max(left + 1);
+    let top = y * height / rows;
+    let bottom = ((y + 1) * height / rows).max(top + 1);
+
+    let mut sum = [0; 3];
+    for row in top..bottom {
+        for pixel in frame[(row * width + left) * 3..(row * width + right) * 3].chunks_exact(3) {
+            for (total, &channel) in sum.iter_mut().zip(pixel) {
+                *total += channel as usize;
+            }
+        }
+    }
+    let count = (right - left) * (bottom - top);
+    [
+        (sum[0] / count) as u8,
+        (sum[1] / count) as u8,
+        (sum[2] / count) as u8,
+    ]
+}
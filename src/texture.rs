@@ -0,0 +1,369 @@
+pub const TEXTURE_WIDTH: usize = 64;
+pub const TEXTURE_HEIGHT: usize = 64;
+// 64x64 down to 1x1
+pub const MIP_LEVELS: usize = 7;
+
+/// How textures are looked up between texel centers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filtering {
+    Nearest,
+    Bilinear,
+}
+
+/// A square texture and its mip chain. Every level is stored column by column
+/// (`size * x + y`) so walls and sprites, which are drawn a column at a time, read their
+/// texels in order.
+pub struct Texture {
+    mips: Vec<Vec<u8>>,
+    animation: Option<Animated>,
+}
+
+/// The frames of an animated texture, as indices of other textures.
+struct Animated {
+    frames: Vec<usize>,
+    rate: f64,
+    playback: Playback,
+}
+
+impl Texture {
+    /// Builds the mip chain for a column-major `TEXTURE_WIDTH` by `TEXTURE_HEIGHT` texture,
+    /// each level averaging 2x2 texels of the one above.
+    pub fn new(pixels: Vec<u8>) -> Texture {
+        let mut mips = vec![pixels];
+        for level in 1..MIP_LEVELS {
+            let size = TEXTURE_HEIGHT >> level;
+            let above = &mips[level - 1];
+            let mut mip = vec![0; size * size * 3];
+            for x in 0..size {
+                for y in 0..size {
+                    for i in 0..3 {
+                        let texel = |x: usize, y: usize| above[(size * 2 * x + y) * 3 + i] as u32;
+                        let sum = texel(x * 2, y * 2)
+                            + texel(x * 2 + 1, y * 2)
+                            + texel(x * 2, y * 2 + 1)
+                            + texel(x * 2 + 1, y * 2 + 1);
+                        mip[(size * x + y) * 3 + i] = ((sum + 2) / 4) as u8;
+                    }
+                }
+            }
+            mips.push(mip);
+        }
+
+        Texture {
+            mips,
+            animation: None,
+        }
+    }
+
+    /// An animation through the `frames` of `textures`, `rate` frames a second. It looks like
+    /// its first frame wherever it isn't looked up with `current_frame`.
+    pub fn animated(
+        textures: &[Texture],
+        frames: Vec<usize>,
+        rate: f64,
+        playback: Playback,
+    ) -> Texture {
+        Texture {
+            mips: textures[frames[0]].mips.clone(),
+            animation: Some(Animated {
+                frames,
+                rate,
+                playback,
+            }),
+        }
+    }
+
+    /// The full size texture.
+    pub fn pixels(&self) -> &[u8] {
+        &self.mips[0]
+    }
+
+    /// Mip `level`, `TEXTURE_HEIGHT >> level` texels on a side.
+    pub fn mip(&self, level: usize) -> &[u8] {
+        &self.mips[level]
+    }
+
+    /// Blends the four texels of mip `level` around `(u, v)`, given in that level's texels,
+    /// wrapping around the edges like a tiled wall or floor.
+    pub fn sample_bilinear(&self, level: usize, u: f64, v: f64) -> [u8; 3] {
+        let size = TEXTURE_HEIGHT >> level;
+        let mip = &self.mips[level];
+        let ((x0, x1, fx), (y0, y1, fy)) =
+            (bilinear_taps(u, size, true), bilinear_taps(v, size, true));
+
+        let mut color = [0; 3];
+        for (i, component) in color.iter_mut().enumerate() {
+            let texel = |x: usize, y: usize| mip[(size * x + y) * 3 + i] as f64;
+            let top = texel(x0, y0) * (1.0 - fx) + texel(x1, y0) * fx;
+            let bottom = texel(x0, y1) * (1.0 - fx) + texel(x1, y1) * fx;
+            *component = (top * (1.0 - fy) + bottom * fy).round() as u8;
+        }
+        color
+    }
+
+    /// Blends the full size texels around `(u, v)` like a sprite, clamping at the edges and
+    /// leaving out black, transparent texels. Returns `None` where the point is mostly
+    /// transparent.
+    pub fn sample_bilinear_masked(&self, u: f64, v: f64) -> Option<[u8; 3]> {
+        let pixels = &self.mips[0];
+        let ((x0, x1, fx), (y0, y1, fy)) = (
+            bilinear_taps(u, TEXTURE_WIDTH, false),
+            bilinear_taps(v, TEXTURE_HEIGHT, false),
+        );
+
+        let mut sum = [0.0; 3];
+        let mut coverage = 0.0;
+        for &(x, y, weight) in [
+            (x0, y0, (1.0 - fx) * (1.0 - fy)),
+            (x1, y0, fx * (1.0 - fy)),
+            (x0, y1, (1.0 - fx) * fy),
+            (x1, y1, fx * fy),
+        ]
+        .iter()
+        {
+            let texel = &pixels[(TEXTURE_HEIGHT * x + y) * 3..][..3];
+            if texel != [0, 0, 0] {
+                for (total, component) in sum.iter_mut().zip(texel) {
+                    *total += *component as f64 * weight;
+                }
+                coverage += weight;
+            }
+        }
+
+        if coverage < 0.5 {
+            return None;
+        }
+        let mut color = [0; 3];
+        for (component, total) in color.iter_mut().zip(sum.iter()) {
+            *component = (total / coverage).round() as u8;
+        }
+        Some(color)
+    }
+}
+
+/// The two texels either side of `coordinate` along one axis of a `size` texel texture, and
+/// how far it is from the first towards the second.
+fn bilinear_taps(coordinate: f64, size: usize, wrap: bool) -> (usize, usize, f64) {
+    // texel centers sit half way across each texel
+    let coordinate = coordinate - 0.5;
+    let first = coordinate.floor();
+    let fraction = coordinate - first;
+    let first = first as i64;
+    if wrap {
+        let size = size as i64;
+        (
+            first.rem_euclid(size) as usize,
+            (first + 1).rem_euclid(size) as usize,
+            fraction,
+        )
+    } else {
+        let last = size as i64 - 1;
+        (
+            first.clamp(0, last) as usize,
+            (first + 1).clamp(0, last) as usize,
+            fraction,
+        )
+    }
+}
+
+/// Picks the mip level for drawing with `texels_per_pixel` texels of the full size texture
+/// covering each pixel.
+pub fn mip_level(texels_per_pixel: f64) -> usize {
+    if texels_per_pixel <= 1.0 {
+        0
+    } else {
+        (texels_per_pixel.log2() as usize).min(MIP_LEVELS - 1)
+    }
+}
+
+/// Every texture image by name, in the order they're indexed.
+const TEXTURES: [(&str, &[u8]); 26] = [
+    ("eagle", include_bytes!("textures/eagle.png")),
+    ("redbrick", include_bytes!("textures/redbrick.png")),
+    ("purplestone", include_bytes!("textures/purplestone.png")),
+    ("greystone", include_bytes!("textures/greystone.png")),
+    ("bluestone", include_bytes!("textures/bluestone.png")),
+    ("mossy", include_bytes!("textures/mossy.png")),
+    ("wood", include_bytes!("textures/wood.png")),
+    ("colorstone", include_bytes!("textures/colorstone.png")),
+    ("barrel", include_bytes!("textures/barrel.png")),
+    ("pillar", include_bytes!("textures/pillar.png")),
+    ("greenlight", include_bytes!("textures/greenlight.png")),
+    (
+        "greenlight_dim",
+        include_bytes!("textures/greenlight_dim.png"),
+    ),
+    ("water0", include_bytes!("textures/water0.png")),
+    ("water1", include_bytes!("textures/water1.png")),
+    ("water2", include_bytes!("textures/water2.png")),
+    ("water3", include_bytes!("textures/water3.png")),
+    ("bullethole", include_bytes!("textures/bullethole.png")),
+    ("blood", include_bytes!("textures/blood.png")),
+    ("guard0", include_bytes!("textures/guard0.png")),
+    ("guard1", include_bytes!("textures/guard1.png")),
+    ("guard2", include_bytes!("textures/guard2.png")),
+    ("guard3", include_bytes!("textures/guard3.png")),
+    ("guard4", include_bytes!("textures/guard4.png")),
+    ("guard5", include_bytes!("textures/guard5.png")),
+    ("guard6", include_bytes!("textures/guard6.png")),
+    ("guard7", include_bytes!("textures/guard7.png")),
+];
+
+/// How an animation steps through its frames.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Playback {
+    /// Back to the first frame after the last.
+    Loop,
+    /// Back and forth between the first and last frames.
+    PingPong,
+}
+
+/// A texture that cycles through the textures named in `frames`, `rate` frames a second.
+/// Animations are indexed after every texture image, so they can be used anywhere one can.
+/// Adding an image moves them along, so they're best found by name with `texture`.
+pub struct Animation {
+    pub name: &'static str,
+    pub frames: &'static [&'static str],
+    pub rate: f64,
+    pub playback: Playback,
+}
+
+pub const ANIMATIONS: [Animation; 2] = [
+    // a lamp on a bad wire
+    Animation {
+        name: "flickering_light",
+        frames: &[
+            "greenlight",
+            "greenlight",
+            "greenlight",
+            "greenlight_dim",
+            "greenlight",
+            "greenlight_dim",
+            "greenlight_dim",
+            "greenlight",
+        ],
+        rate: 10.0,
+        playback: Playback::Loop,
+    },
+    // water running down a wall
+    Animation {
+        name: "water",
+        frames: &["water0", "water1", "water2", "water3"],
+        rate: 6.0,
+        playback: Playback::Loop,
+    },
+];
+
+/// The index of the texture or animation called `name`.
+pub const fn texture_index(name: &str) -> Option<usize> {
+    let mut index = 0;
+    while index < TEXTURES.len() {
+        if names_match(TEXTURES[index].0, name) {
+            return Some(index);
+        }
+        index += 1;
+    }
+    let mut animation = 0;
+    while animation < ANIMATIONS.len() {
+        if names_match(ANIMATIONS[animation].name, name) {
+            return Some(TEXTURES.len() + animation);
+        }
+        animation += 1;
+    }
+    None
+}
+
+/// The index of the texture or animation called `name`, for naming textures in constants,
+/// where a name that isn't there fails to compile.
+pub const fn texture(name: &str) -> usize {
+    match texture_index(name) {
+        Some(index) => index,
+        None => panic!("no texture or animation has that name"),
+    }
+}
+
+// `==` on strings isn't usable in constants
+const fn names_match(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// The index of the texture `index` shows at `time` in seconds, which is itself unless it's
+/// an animation.
+pub fn current_frame(textures: &[Texture], index: usize, time: f64) -> usize {
+    let animation = match &textures[index].animation {
+        Some(animation) => animation,
+        None => return index,
+    };
+    let count = animation.frames.len();
+    let step = (time * animation.rate).floor().max(0.0) as usize;
+    let frame = match animation.playback {
+        Playback::PingPong if count > 1 => {
+            let step = step % (2 * count - 2);
+            step.min(2 * count - 2 - step)
+        }
+        _ => step % count,
+    };
+    animation.frames[frame]
+}
+
+/// Loads every texture in `TEXTURES` and builds their mip chains, followed by the animations
+/// in `ANIMATIONS`.
+pub fn load_textures() -> Result<Vec<Texture>, String> {
+    let mut textures = Vec::new();
+    for &(_, png) in TEXTURES.iter() {
+        textures.push(Texture::new(transpose(&load_png(png)?)));
+    }
+
+    for animation in ANIMATIONS.iter() {
+        let mut frames = Vec::new();
+        for &frame in animation.frames {
+            match texture_index(frame) {
+                Some(index) if index < TEXTURES.len() => frames.push(index),
+                _ => {
+                    return Err(format!(
+                        "animation {} has no texture {}",
+                        animation.name, frame
+                    ))
+                }
+            }
+        }
+        let animated = Texture::animated(&textures, frames, animation.rate, animation.playback);
+        textures.push(animated);
+    }
+
+    Ok(textures)
+}
+
+fn transpose(texture: &[u8]) -> Vec<u8> {
+    let mut transposed = vec![0; texture.len()];
+    for y in 0..TEXTURE_HEIGHT {
+        for x in 0..TEXTURE_WIDTH {
+            let from = (TEXTURE_WIDTH * y + x) * 3;
+            let to = (TEXTURE_HEIGHT * x + y) * 3;
+            transposed[to..to + 3].clone_from_slice(&texture[from..from + 3]);
+        }
+    }
+    transposed
+}
+
+pub fn load_png(texture: impl std::io::Read) -> Result<Vec<u8>, String> {
+    let decoder = png::Decoder::new(texture);
+    let (info, mut reader) = decoder.read_info().map_err(|e| format!("{:?}", e))?;
+    let mut buf = vec![0; info.buffer_size()];
+    reader
+        .next_frame(&mut buf)
+        .map_err(|e| format!("{:?}", e))?;
+
+    Ok(buf)
+}
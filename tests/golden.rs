@@ -0,0 +1,579 @@
+//! Renders fixed camera poses and compares them against the PNGs in `tests/golden`.
+//!
+//! Run with `UPDATE_GOLDEN=1` to regenerate the golden images after an intentional change to
+//! the renderer.
+
+use std::env;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use wolf_rust::camera::Camera;
+use wolf_rust::decal::Decal;
+use wolf_rust::fixed::{Fixed, Scalar};
+use wolf_rust::map::Face;
+use wolf_rust::postprocess::{Pass, PostProcess};
+use wolf_rust::render::{Renderer, SCREEN_HEIGHT, SCREEN_WIDTH};
+use wolf_rust::screenshot::save_png;
+use wolf_rust::sprite::SPRITES;
+use wolf_rust::stereo::Stereo;
+use wolf_rust::supersample::Downsample;
+use wolf_rust::texture::{
+    current_frame, load_png, load_textures, texture_index, Filtering, Playback, Texture,
+};
+
+// how far a single channel may drift before the pixel counts as different
+const CHANNEL_TOLERANCE: i16 = 8;
+// fraction of the frame allowed to differ
+const MAX_MISMATCHED_PIXELS: f64 = 0.005;
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.png", name))
+}
+
+fn render_frame(camera: &Camera, width: usize, height: usize) -> Vec<u8> {
+    render_frame_with_threads(camera, width, height, 1)
+}
+
+fn render_frame_with_threads(
+    camera: &Camera,
+    width: usize,
+    height: usize,
+    threads: usize,
+) -> Vec<u8> {
+    let mut renderer = Renderer::new(width, height);
+    renderer.set_threads(threads);
+    render_frame_with(&mut renderer, camera)
+}
+
+fn render_frame_with(renderer: &mut Renderer, camera: &Camera) -> Vec<u8> {
+    let (width, height) = (renderer.width(), renderer.height());
+    let textures = load_textures().unwrap();
+    let mut texture_buffer = vec![0; width * height * 3];
+    renderer.render(camera, &textures, &SPRITES, &mut texture_buffer);
+    texture_buffer
+}
+
+fn assert_matches_golden(name: &str, camera: Camera) {
+    assert_matches_golden_at(name, camera, SCREEN_WIDTH, SCREEN_HEIGHT);
+}
+
+fn assert_matches_golden_at(name: &str, mut camera: Camera, width: usize, height: usize) {
+    camera.set_aspect_ratio(width, height);
+    let frame = render_frame(&camera, width, height);
+    assert_frame_matches_golden(name, &frame, width, height);
+}
+
+fn assert_frame_matches_golden(name: &str, frame: &[u8], width: usize, height: usize) {
+    let path = golden_path(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        save_png(&path, frame, width, height).unwrap();
+        return;
+    }
+
+    let golden = load_png(File::open(&path).unwrap()).unwrap();
+    assert_eq!(golden.len(), frame.len(), "{} has the wrong size", name);
+
+    let mismatched = frame
+        .chunks(3)
+        .zip(golden.chunks(3))
+        .filter(|(actual, expected)| {
+            actual
+                .iter()
+                .zip(expected.iter())
+                .any(|(a, e)| (*a as i16 - *e as i16).abs() > CHANNEL_TOLERANCE)
+        })
+        .count();
+    let allowed = (MAX_MISMATCHED_PIXELS * (width * height) as f64) as usize;
+    assert!(
+        mismatched <= allowed,
+        "{} differs from {} in {} pixels (allowed {})",
+        name,
+        path.display(),
+        mismatched,
+        allowed
+    );
+}
+
+#[test]
+fn fisheye_pillar_row() {
+    // the player start looks straight at the row of pillars in front of the wall
+    assert_matches_golden("pillar_row", Camera::default());
+}
+
+#[test]
+fn sprite_sorting() {
+    // the barrels in the north-east room overlap from this angle
+    assert_matches_golden("barrel_cluster", Camera::new(18.5, 2.5, -1.0, -0.4));
+}
+
+#[test]
+fn floor_casting() {
+    // looking down the long corridor along the south wall
+    assert_matches_golden("corridor_floor", Camera::new(20.5, 1.5, 0.0, 1.0));
+}
+
+#[test]
+fn widescreen_pillar_row() {
+    // a 16:9 frame widens the view instead of stretching the walls
+    assert_matches_golden_at("pillar_row_widescreen", Camera::default(), 854, 480);
+}
+
+#[test]
+fn half_resolution() {
+    assert_matches_golden_at("pillar_row_half", Camera::default(), 320, 240);
+}
+
+#[test]
+fn odd_resolutions() {
+    // an odd height puts the horizon half a pixel off the middle, which used to push sprite
+    // texels past the top of their texture
+    let camera = Camera::new(22.0, 11.5, -0.07, -1.0);
+    for &(width, height) in [(213, 159), (161, 121)].iter() {
+        let mut camera = camera;
+        camera.set_aspect_ratio(width, height);
+        for &fixed_point in [false, true].iter() {
+            let mut renderer = Renderer::new(width, height);
+            renderer.set_fixed_point(fixed_point);
+            let frame = render_frame_with(&mut renderer, &camera);
+            assert_eq!(frame.len(), width * height * 3);
+        }
+    }
+}
+
+#[test]
+fn wide_field_of_view() {
+    // walls, floor and sprites all shrink together instead of stretching sideways
+    let mut camera = Camera::default();
+    camera.set_fov(100.0);
+    assert_matches_golden("pillar_row_wide_fov", camera);
+}
+
+#[test]
+fn threads_match_single_thread() {
+    // odd sizes so the bands and strips don't divide evenly
+    let (width, height) = (333, 251);
+    let poses = [
+        Camera::default(),
+        Camera::new(18.5, 2.5, -1.0, -0.4),
+        Camera::new(20.5, 1.5, 0.0, 1.0),
+    ];
+    for pose in poses.iter() {
+        let mut camera = *pose;
+        camera.set_aspect_ratio(width, height);
+        let expected = render_frame_with_threads(&camera, width, height, 1);
+        for threads in [2, 3, 8, 400].iter() {
+            let actual = render_frame_with_threads(&camera, width, height, *threads);
+            assert!(
+                actual == expected,
+                "{} threads differ from a single thread at {:?}",
+                threads,
+                camera
+            );
+        }
+    }
+}
+
+#[test]
+fn threads_outnumber_pixels() {
+    // fewer columns and rows than threads leaves some of them without any, and an empty eye
+    // of a side-by-side pair leaves all of them without any
+    for (width, height) in [(1, 120), (160, 1), (3, 2), (0, 120)].iter() {
+        let mut camera = Camera::default();
+        camera.set_aspect_ratio(*width, *height);
+        let expected = render_frame_with_threads(&camera, *width, *height, 1);
+        let actual = render_frame_with_threads(&camera, *width, *height, 8);
+        assert!(
+            actual == expected,
+            "8 threads differ at {}x{}",
+            width,
+            height
+        );
+
+        let mut renderer = Renderer::new(*width, *height);
+        renderer.set_threads(8);
+        renderer.set_supersampling(2);
+        render_frame_with(&mut renderer, &camera);
+    }
+}
+
+#[test]
+fn mipmapping_off() {
+    // the far end of the corridor shimmers without mipmaps
+    let mut renderer = Renderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    renderer.set_mipmapping(false);
+    let frame = render_frame_with(&mut renderer, &Camera::new(20.5, 1.5, 0.0, 1.0));
+    assert_frame_matches_golden(
+        "corridor_floor_no_mipmaps",
+        &frame,
+        SCREEN_WIDTH,
+        SCREEN_HEIGHT,
+    );
+}
+
+#[test]
+fn bilinear_filtering() {
+    let mut renderer = Renderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    renderer.set_filtering(Filtering::Bilinear);
+    let frame = render_frame_with(&mut renderer, &Camera::new(18.5, 2.5, -1.0, -0.4));
+    assert_frame_matches_golden(
+        "barrel_cluster_bilinear",
+        &frame,
+        SCREEN_WIDTH,
+        SCREEN_HEIGHT,
+    );
+}
+
+#[test]
+fn supersampling() {
+    let mut renderer = Renderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    renderer.set_supersampling(2);
+    renderer.set_downsample(Downsample::Tent);
+    let frame = render_frame_with(&mut renderer, &Camera::new(18.5, 2.5, -1.0, -0.4));
+    assert_frame_matches_golden("barrel_cluster_ssaa", &frame, SCREEN_WIDTH, SCREEN_HEIGHT);
+}
+
+#[test]
+fn fixed_point_matches_float() {
+    let poses = [
+        Camera::default(),
+        Camera::new(18.5, 2.5, -1.0, -0.4),
+        Camera::new(20.5, 1.5, 0.0, 1.0),
+        Camera::new(9.5, 4.5, 0.6, 0.8),
+        Camera::new(10.2, 3.5, -1.0, -1.0),
+        Camera::new(3.2, 12.5, -0.2, 1.0),
+        Camera::new(5.4, 3.5, 0.1, -1.0),
+        // rippling water, with the sine worked out in fixed point
+        Camera::new(14.9, 10.5, 0.1, 1.0),
+    ];
+    for camera in poses.iter() {
+        let expected = render_frame(camera, SCREEN_WIDTH, SCREEN_HEIGHT);
+        let mut renderer = Renderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+        renderer.set_fixed_point(true);
+        let actual = render_frame_with(&mut renderer, camera);
+
+        // every pixel has to show up within a pixel of where the float path put it
+        let pixel = |frame: &[u8], x: usize, y: usize| {
+            let start = (y * SCREEN_WIDTH + x) * 3;
+            [frame[start], frame[start + 1], frame[start + 2]]
+        };
+        let mut mismatched = 0;
+        for y in 0..SCREEN_HEIGHT {
+            for x in 0..SCREEN_WIDTH {
+                let color = pixel(&actual, x, y);
+                let found = (y.saturating_sub(1)..(y + 2).min(SCREEN_HEIGHT)).any(|ny| {
+                    (x.saturating_sub(1)..(x + 2).min(SCREEN_WIDTH)).any(|nx| {
+                        let other = pixel(&expected, nx, ny);
+                        color
+                            .iter()
+                            .zip(other.iter())
+                            .all(|(a, e)| (*a as i16 - *e as i16).abs() <= CHANNEL_TOLERANCE)
+                    })
+                });
+                if !found {
+                    mismatched += 1;
+                }
+            }
+        }
+        let allowed = (MAX_MISMATCHED_PIXELS * (SCREEN_WIDTH * SCREEN_HEIGHT) as f64) as usize;
+        assert!(
+            mismatched <= allowed,
+            "fixed point differs from floating point in {} pixels (allowed {}) at {:?}",
+            mismatched,
+            allowed,
+            camera
+        );
+    }
+}
+
+#[test]
+fn simd_matches_scalar() {
+    // odd sizes so the rows don't split evenly into vectors
+    let (width, height) = (333, 251);
+    let poses = [
+        Camera::default(),
+        Camera::new(18.5, 2.5, -1.0, -0.4),
+        Camera::new(20.5, 1.5, 0.0, 1.0),
+    ];
+    for pose in poses.iter() {
+        let mut camera = *pose;
+        camera.set_aspect_ratio(width, height);
+        let mut renderer = Renderer::new(width, height);
+        renderer.set_simd(false);
+        let expected = render_frame_with(&mut renderer, &camera);
+        renderer.set_simd(true);
+        let actual = render_frame_with(&mut renderer, &camera);
+        assert!(
+            actual == expected,
+            "the vectorized floor differs from the scalar one at {:?}",
+            camera
+        );
+    }
+}
+
+#[test]
+fn post_processing() {
+    let mut renderer = Renderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    renderer.set_post_process(PostProcess::new(vec![
+        Pass::Curvature { amount: 0.1 },
+        Pass::Scanlines { strength: 0.3 },
+        Pass::Vignette { strength: 0.5 },
+        Pass::Dither { bits: 4 },
+    ]));
+    let frame = render_frame_with(&mut renderer, &Camera::default());
+    assert_frame_matches_golden("pillar_row_crt", &frame, SCREEN_WIDTH, SCREEN_HEIGHT);
+
+    // turned off, the chain is skipped
+    renderer.set_post_processing(false);
+    let frame = render_frame_with(&mut renderer, &Camera::default());
+    assert_frame_matches_golden("pillar_row", &frame, SCREEN_WIDTH, SCREEN_HEIGHT);
+}
+
+#[test]
+fn identity_lut_keeps_frame() {
+    // the corners of the color cube, red changing fastest
+    let mut cube = String::from("LUT_3D_SIZE 2\n");
+    for blue in 0..2 {
+        for green in 0..2 {
+            for red in 0..2 {
+                cube += &format!("{} {} {}\n", red, green, blue);
+            }
+        }
+    }
+    let directory = env::temp_dir().join("wolf_rust_identity_lut");
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("identity.cube"), cube).unwrap();
+
+    let mut renderer = Renderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let camera = Camera::new(18.5, 2.5, -1.0, -0.4);
+    let expected = render_frame_with(&mut renderer, &camera);
+    renderer.set_post_process(PostProcess::parse("lut identity.cube", &directory).unwrap());
+    let actual = render_frame_with(&mut renderer, &camera);
+    assert!(actual == expected, "the identity LUT changed the frame");
+}
+
+#[test]
+fn post_process_errors_name_the_line() {
+    let error = PostProcess::parse("scanlines 0.3\nbloom 1", Path::new("")).unwrap_err();
+    assert_eq!(error, "line 2: unknown pass bloom");
+    let error = PostProcess::parse("# strong\nvignette 2", Path::new("")).unwrap_err();
+    assert_eq!(error, "line 2: expected an amount from 0 to 1");
+}
+
+#[test]
+fn stereo() {
+    let mut renderer = Renderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    renderer.set_stereo(Stereo::Anaglyph);
+    let frame = render_frame_with(&mut renderer, &Camera::default());
+    assert_frame_matches_golden("pillar_row_anaglyph", &frame, SCREEN_WIDTH, SCREEN_HEIGHT);
+
+    renderer.set_stereo(Stereo::SideBySide);
+    let frame = render_frame_with(&mut renderer, &Camera::new(18.5, 2.5, -1.0, -0.4));
+    assert_frame_matches_golden(
+        "barrel_cluster_side_by_side",
+        &frame,
+        SCREEN_WIDTH,
+        SCREEN_HEIGHT,
+    );
+
+    // too narrow to split between the eyes, on more threads than there are columns
+    let mut renderer = Renderer::new(1, SCREEN_HEIGHT);
+    renderer.set_threads(4);
+    renderer.set_stereo(Stereo::SideBySide);
+    let frame = render_frame_with(&mut renderer, &Camera::default());
+    assert_frame_matches_golden("pillar_row_side_by_side_narrow", &frame, 1, SCREEN_HEIGHT);
+}
+
+#[test]
+fn diagonal_walls() {
+    // the north-west corner of the room west of the start is cut off
+    assert_matches_golden("diagonal_corner", Camera::new(10.2, 3.5, -1.0, -1.0));
+
+    // walking into it stops at the diagonal, where x + y = 10
+    let mut camera = Camera::new(10.2, 3.5, -1.0, -1.0);
+    for _ in 0..100 {
+        camera.move_forward(0.05);
+    }
+    assert!(
+        camera.pos_x + camera.pos_y >= 10.0,
+        "walked into {:?}",
+        camera
+    );
+    assert!(
+        camera.pos_x + camera.pos_y < 10.1,
+        "stopped short at {:?}",
+        camera
+    );
+}
+
+#[test]
+fn round_pillars() {
+    assert_matches_golden("round_pillars", Camera::new(3.2, 12.5, -0.2, 1.0));
+
+    // walking into a pillar stops at its edge, 0.3 from the middle of the cell
+    let mut camera = Camera::new(2.5, 13.5, 0.0, 1.0);
+    for _ in 0..100 {
+        camera.move_forward(0.05);
+    }
+    let distance = (camera.pos_x - 2.5).hypot(camera.pos_y - 15.5);
+    assert!(distance >= 0.3, "walked into the pillar at {:?}", camera);
+    assert!(distance < 0.4, "stopped short at {:?}", camera);
+}
+
+#[test]
+fn portals() {
+    // the left wall of the room at the top left of the map looks out of its top wall, with the
+    // lights down the middle of the room showing through it
+    assert_matches_golden("portal_view", Camera::new(5.4, 3.5, 0.1, -1.0));
+
+    // right up against the turned portal most of the floor and ceiling are past it, and run
+    // the way they do on the far side
+    assert_matches_golden("portal_floor", Camera::new(5.5, 1.6, 0.3, -1.0));
+
+    // walking through the top wall comes out of the left wall, turned to face into the room
+    let mut camera = Camera::new(2.5, 5.5, -1.0, 0.0);
+    for _ in 0..40 {
+        camera.move_forward(0.05);
+    }
+    assert!(
+        (camera.pos_x - 5.5).abs() < 1e-6 && (camera.pos_y - 1.5).abs() < 1e-6,
+        "came out at {:?}",
+        camera
+    );
+    assert!(
+        camera.dir_x.abs() < 1e-9 && (camera.dir_y - 1.0).abs() < 1e-9,
+        "facing {:?}",
+        camera
+    );
+}
+
+#[test]
+fn mirrors() {
+    // the end of the long corridor reflects it, and the pillar beside it stands in front of its
+    // own reflection
+    assert_matches_golden("mirror_wall", Camera::new(20.2, 4.5, 0.3, -1.0));
+}
+
+#[test]
+fn reflective_floors() {
+    // the far wall of the small room shows in the pool of water and the marble around it
+    let camera = Camera::new(14.9, 10.5, 0.1, 1.0);
+    assert_matches_golden("reflective_floor", camera);
+
+    // the water ripples as time goes on
+    let mut renderer = Renderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let still = render_frame_with(&mut renderer, &camera);
+    renderer.set_time(0.3);
+    assert_ne!(still, render_frame_with(&mut renderer, &camera));
+}
+
+#[test]
+fn face_textures() {
+    // one face of the hall's wall has an eagle on it and the next a wooden conveyor
+    let camera = Camera::new(10.5, 10.5, -0.5, 1.0);
+    assert_matches_golden("face_textures", camera);
+
+    // the conveyor scrolls as time goes on
+    let mut renderer = Renderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let still = render_frame_with(&mut renderer, &camera);
+    renderer.set_time(0.5);
+    assert_ne!(still, render_frame_with(&mut renderer, &camera));
+}
+
+#[test]
+fn animated_textures() {
+    let textures = load_textures().unwrap();
+    let light = texture_index("greenlight").unwrap();
+    let dim = texture_index("greenlight_dim").unwrap();
+    let flickering = texture_index("flickering_light").unwrap();
+    assert_eq!(current_frame(&textures, light, 0.35), light);
+    assert_eq!(current_frame(&textures, flickering, 0.0), light);
+    assert_eq!(current_frame(&textures, flickering, 0.35), dim);
+    // it loops back to the start after its eight frames
+    assert_eq!(current_frame(&textures, flickering, 1.05), light);
+
+    // ping-pong goes back down through the frames instead of jumping to the first
+    let bounce = Texture::animated(&textures, vec![0, 1, 2], 1.0, Playback::PingPong);
+    let mut textures = textures;
+    textures.push(bounce);
+    let frames: Vec<usize> = (0..6)
+        .map(|second| current_frame(&textures, textures.len() - 1, second as f64 + 0.5))
+        .collect();
+    assert_eq!(frames, [0, 1, 2, 1, 0, 1]);
+
+    // the lamp in the hall dims a moment later
+    let camera = Camera::new(10.5, 10.5, -0.5, 1.0);
+    let mut renderer = Renderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let lit = render_frame_with(&mut renderer, &camera);
+    renderer.set_time(0.35);
+    assert_ne!(lit, render_frame_with(&mut renderer, &camera));
+}
+
+#[test]
+fn decals() {
+    // a poster, blood and a bullet hole on the far wall of the room at the bottom right
+    let camera = Camera::new(9.5, 21.5, -0.15, -1.0);
+    assert_matches_golden("decals", camera);
+
+    // decals stamped on while playing show up too, until newer ones push them out
+    let mut renderer = Renderer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let bare = render_frame_with(&mut renderer, &camera);
+    let hole = Decal {
+        cell: (9, 17),
+        face: Face::MaxY,
+        u: 0.2,
+        v: 0.8,
+        size: 0.2,
+        texture: texture_index("bullethole").unwrap(),
+    };
+    renderer.decals_mut().add(hole);
+    assert_ne!(bare, render_frame_with(&mut renderer, &camera));
+
+    let elsewhere = Decal {
+        cell: (1, 1),
+        ..hole
+    };
+    for _ in 0..renderer.decals().capacity() {
+        renderer.decals_mut().add(elsewhere);
+    }
+    assert_eq!(renderer.decals().len(), renderer.decals().capacity());
+    assert!(renderer.decals().iter().all(|&decal| decal == elsewhere));
+    assert_eq!(bare, render_frame_with(&mut renderer, &camera));
+}
+
+#[test]
+fn scaled_sprites() {
+    // a half-size barrel on the floor and a half-size lamp hanging from the ceiling
+    assert_matches_golden("scaled_sprites", Camera::new(9.5, 20.5, 1.0, -0.3));
+}
+
+#[test]
+fn directional_sprites() {
+    // the guard in the long hall, seen from in front and off to one side
+    assert_matches_golden("directional_sprite", Camera::new(10.6, 7.0, -1.1, -1.5));
+
+    // it faces down the hall toward larger y, and the frames go around toward larger angles
+    let guard = SPRITES
+        .iter()
+        .find(|sprite| sprite.rotations.is_some())
+        .unwrap();
+    let frames = guard.rotations.unwrap().frames;
+    let sides = [
+        (0.0, 3.0, 0),
+        (-3.0, 3.0, 1),
+        (-3.0, 0.0, 2),
+        (0.0, -3.0, 4),
+        (3.0, 0.0, 6),
+        (3.0, 3.0, 7),
+        // a little off to one side still shows the front
+        (0.5, 3.0, 0),
+    ];
+    for &(offset_x, offset_y, frame) in sides.iter() {
+        let (x, y) = (guard.x + offset_x, guard.y + offset_y);
+        assert_eq!(guard.texture_from(x, y), frames[frame]);
+        // fixed point picks the same side
+        let fixed = guard.texture_from(Fixed::from_f64(x), Fixed::from_f64(y));
+        assert_eq!(fixed, frames[frame]);
+    }
+}
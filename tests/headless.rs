@@ -0,0 +1,91 @@
+//! Runs the game loop without a display, driven by scripted input.
+
+use wolf_rust::backend::{parse_script, Headless};
+use wolf_rust::camera::Camera;
+use wolf_rust::game;
+use wolf_rust::map::{trace, Face};
+use wolf_rust::render::Renderer;
+use wolf_rust::texture::load_textures;
+
+fn run_script(script: &str, camera: &mut Camera) -> Vec<Vec<u8>> {
+    let textures = load_textures().unwrap();
+    let mut renderer = Renderer::new(160, 120);
+    camera.set_aspect_ratio(160, 120);
+
+    let mut frames = Vec::new();
+    let mut backend = Headless::new(parse_script(script).unwrap(), |frame: &[u8], _, _| {
+        frames.push(frame.to_vec());
+        Ok(())
+    });
+    game::run(&mut backend, &mut renderer, camera, &textures).unwrap();
+    frames
+}
+
+#[test]
+fn empty_script_shows_one_frame() {
+    let mut camera = Camera::default();
+    let frames = run_script("", &mut camera);
+    assert_eq!(frames.len(), 1);
+    assert_eq!(camera, {
+        let mut start = Camera::default();
+        start.set_aspect_ratio(160, 120);
+        start
+    });
+}
+
+#[test]
+fn scripted_walk() {
+    let mut camera = Camera::default();
+    let frames = run_script(
+        "# half a second forward at 60 frames a second\n\
+         0 press forward\n\
+         30 release forward\n",
+        &mut camera,
+    );
+
+    // the press lands after frame 0 and the release after frame 30, which is the last
+    assert_eq!(frames.len(), 31);
+    assert!((camera.pos_x - (22.0 - 2.5)).abs() < 1e-9, "{:?}", camera);
+    assert_eq!(camera.pos_y, 11.5);
+    assert!(frames[0] != frames[30]);
+}
+
+#[test]
+fn script_errors_name_the_line() {
+    assert_eq!(
+        parse_script("0 press forward\n1 hold forward").unwrap_err(),
+        "line 2: expected <frame> <press|release> <action>"
+    );
+    assert_eq!(
+        parse_script("0 press jump").unwrap_err(),
+        "line 1: unknown action jump"
+    );
+}
+
+#[test]
+fn shooting_marks_the_wall() {
+    // facing the far wall of the room at the bottom right from a couple of cells away
+    let mut camera = Camera::new(9.5, 20.5, 0.0, -1.0);
+    let frames = run_script("0 press shoot\n1 release shoot\n", &mut camera);
+    assert_eq!(frames.len(), 2);
+    assert!(frames[0] != frames[1]);
+}
+
+#[test]
+fn shooting_through_a_portal() {
+    // facing the left wall of the room at the top left of the map, which looks out of its top
+    // wall and down the room to the wall at the far end
+    let mut camera = Camera::new(1.5, 5.5, -1.0, 0.0);
+    let hit = trace(camera.pos_x, camera.pos_y, camera.dir_x, camera.dir_y).unwrap();
+    assert_eq!((hit.cell, hit.face), ((5, 10), Face::MinY), "{:?}", hit);
+    assert!(
+        (hit.u - 0.5).abs() < 1e-9 && (hit.distance - 9.5).abs() < 1e-9,
+        "{:?}",
+        hit
+    );
+
+    // so the hole is left where it's seen rather than behind the portal
+    let frames = run_script("0 press shoot\n1 release shoot\n", &mut camera);
+    assert_eq!(frames.len(), 2);
+    assert!(frames[0] != frames[1]);
+}